@@ -1,83 +1,174 @@
-use std::error::Error;
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
-use std::fs;
-use actix_web::{main, get, post, web, App, HttpResponse, HttpServer, Responder};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use actix_web::body::{BodySize, MessageBody};
+use actix_web::{delete, get, patch, post, put, web, HttpResponse, Responder};
+use futures_util::StreamExt;
+use tokio::io::{AsyncRead, AsyncWriteExt, ReadBuf};
 use serde::{ Deserialize, Serialize};
-use crate::{download, models, utils, wallpaper};
-
-#[derive(Deserialize)]
-struct SearchParams {
-    topic: String,
-    page: u16
-}
+use crate::{collections, config, daily, download, history, library, models, scheduler, sources, state, utils, wallpaper};
+use crate::models::wallpaper::SearchPage;
+use crate::sources::{SearchQuery, SourceError};
 
 #[derive(Deserialize)]
 struct ChangeWallpaperParams {
     id: Option<String>,
+    // Defaults to wallhaven
+    provider: Option<String>,
+    // fill, scale, center or tile, defaults to the config value
+    mode: Option<String>,
+    lock_screen: Option<bool>,
+    // Only change this monitor, e.g. "HDMI-1"
+    output: Option<String>,
+    // Split the image across all monitors
+    span: Option<bool>,
+}
+
+impl ChangeWallpaperParams {
+    fn to_options(&self) -> Result<wallpaper::ChangeOptions, String> {
+        let mut options = wallpaper::ChangeOptions::from_config();
+        if let Some(mode) = &self.mode {
+            options.mode = mode.parse()?;
+        }
+        if let Some(lock_screen) = self.lock_screen {
+            options.lock_screen = lock_screen;
+        }
+        if self.output.is_some() {
+            if self.span == Some(true) {
+                return Err("span and output can't be used together".to_string());
+            }
+            options.span = false;
+        } else if let Some(span) = self.span {
+            options.span = span;
+        }
+        Ok(options)
+    }
+}
+
+#[derive(Serialize)]
+struct ThumbnailFailure {
+    id: String,
+    error: String,
 }
 
 #[derive(Serialize)]
 struct SearchResponse {
-    data: models::wallhaven::WHSearchResponse,
+    data: SearchPage,
     thumbnail_paths: Vec<String>,
+    // Results without a thumbnail in thumbnail_paths
+    failed_thumbnails: Vec<ThumbnailFailure>,
 }
 
-
-const WALLHAVEN_DIRECT_ID: &str = "https://wallhaven.cc/api/v1/w";
+// Maps provider errors onto the matching HTTP status
+fn source_error_response(e: SourceError) -> HttpResponse {
+    match e {
+        SourceError::InvalidQuery(message) => HttpResponse::BadRequest().body(message),
+        SourceError::NotFound(message) => HttpResponse::NotFound().body(message),
+        SourceError::Request(e) => HttpResponse::InternalServerError().body(format!("Request to provider failed: {}", e)),
+    }
+}
 
 #[get("/search")]
-pub async fn search_theme(params: web::Query<SearchParams>
+pub async fn search_theme(params: web::Query<HashMap<String, String>>
 ) -> impl Responder {
-    println!("Topic: {}", params.topic);
-    println!("Page: {}", params.page);
-
-    // Replace spaces with %20 in the topic
-    let encoded_topic = params.topic.replace(' ', "%20");
+    let query = match SearchQuery::from_params(params.into_inner()) {
+        Ok(query) => query,
+        Err(e) => return HttpResponse::BadRequest().body(e)
+    };
+    let source = match sources::get_or_default(query.provider.as_deref()) {
+        Ok(source) => source,
+        Err(e) => return source_error_response(e)
+    };
+    search_response(source, query).await
+}
 
-    // Create thumbnails directory if it doesn't exist
-    let thumbnails_folder = utils::get_thumbnails_directory();
+// Runs the search and downloads the thumbnails of the results, shared by /search and /similar
+// Both block on the network, so they run on actix's blocking thread pool rather than the worker
+async fn search_response(source: Box<dyn sources::ImageSource>, query: SearchQuery) -> HttpResponse {
+    let result = web::block(move || -> Result<SearchResponse, SourceError> {
+        println!("Provider: {}", source.name());
+        println!("Topic: {}", query.topic);
+        println!("Page: {}", query.page);
 
-    println!("Thumbnails folder: {}", thumbnails_folder.to_str().unwrap());
+        // Create thumbnails directory if it doesn't exist
+        let thumbnails_folder = utils::get_thumbnails_directory();
 
-    if !thumbnails_folder.exists() {
-        std::fs::create_dir_all(&thumbnails_folder)
-            .expect("Failed to create thumbnails directory");
-    }
+        println!("Thumbnails folder: {}", thumbnails_folder.to_str().unwrap());
 
-    let response: Option<models::wallhaven::WHSearchResponse> = utils::create_search_object_response(
-        encoded_topic.clone(),
-        params.page
-    );
+        if !thumbnails_folder.exists() {
+            std::fs::create_dir_all(&thumbnails_folder)
+                .expect("Failed to create thumbnails directory");
+        }
 
-    match response {
-        Some(data) => {
-            // println!("{:?}", data);
+        let data = source.search(&query)?;
 
-            let mut thumbnail_paths: Vec<String> = Vec::new();
+        let concurrency = config::load().thumbnail_concurrency.unwrap_or(download::image::DEFAULT_THUMBNAIL_CONCURRENCY);
+        let thumbnails = download::image::thumbnails(source.as_ref(), &data.data, thumbnails_folder.to_str().unwrap(), concurrency);
 
-            // Download thumbnails for each image
-            for image in &data.data {
-                match download::image::thumbnail(&image, &thumbnails_folder.to_str().unwrap()) {
-                    Ok(path) => {
-                        println!("Successfully downloaded thumbnail for image {}", image.id);
-                        println!("Path: {}", path);
-                        thumbnail_paths.push(path);
-                    },
-                    Err(e) => eprintln!("Failed to download thumbnail for image {}: {}", image.id, e)
+        let mut thumbnail_paths: Vec<String> = Vec::new();
+        let mut failed_thumbnails = Vec::new();
+        for (image, thumbnail) in data.data.iter().zip(thumbnails) {
+            match thumbnail {
+                Ok(path) => thumbnail_paths.push(path),
+                Err(error) => {
+                    eprintln!("Failed to download thumbnail for image {}: {}", image.id, error);
+                    failed_thumbnails.push(ThumbnailFailure { id: image.id.clone(), error });
                 }
             }
+        }
 
-            // Create combined response
-            let combined_response = SearchResponse {
-                data,
-                thumbnail_paths,
-            };
-
+        Ok(SearchResponse {
+            data,
+            thumbnail_paths,
+            failed_thumbnails,
+        })
+    }).await;
+
+    match result {
+        Ok(Ok(response)) => HttpResponse::Ok().json(response),
+        Ok(Err(e)) => {
+            eprintln!("Error: {}", e);
+            source_error_response(e)
+        },
+        Err(e) => HttpResponse::InternalServerError().body(format!("Search failed: {}", e))
+    }
+}
 
+// More like this for any search result, e.g. GET /similar/wallhaven/1pvwjw?by=tags&purity=sfw
+// Other query parameters (provider, page, filters) work the same as in /search
+#[get("/similar/{source}/{id}")]
+pub async fn similar_images(path: web::Path<(String, String)>, params: web::Query<HashMap<String, String>>) -> impl Responder {
+    let (source_name, id) = path.into_inner();
+    let mut params = params.into_inner();
+    let by = match params.remove("by").filter(|by| !by.trim().is_empty()).map(|by| by.parse()).transpose() {
+        Ok(by) => by,
+        Err(e) => return HttpResponse::BadRequest().body(e)
+    };
+    params.remove("topic");
+    let query = match SearchQuery::from_params(params) {
+        Ok(query) => query,
+        Err(e) => return HttpResponse::BadRequest().body(e)
+    };
+    // Looking up the image's tags can mean a request to the provider
+    let query = match web::block(move || sources::similar(&source_name, &id, by, query)).await {
+        Ok(Ok(query)) => query,
+        Ok(Err(e)) => return source_error_response(e),
+        Err(e) => return HttpResponse::InternalServerError().body(format!("Search failed: {}", e))
+    };
+    match sources::get_or_default(query.provider.as_deref()) {
+        Ok(source) => search_response(source, query).await,
+        Err(e) => source_error_response(e)
+    }
+}
 
-            HttpResponse::Ok().json(combined_response)
-        },
-        None => HttpResponse::NotFound().finish()
+#[get("/tags/{id}")]
+pub async fn get_tag(path: web::Path<u64>) -> impl Responder {
+    let id = path.into_inner();
+    match web::block(move || sources::wallhaven::WallhavenSource::tag(id)).await {
+        Ok(Ok(tag)) => HttpResponse::Ok().json(tag),
+        Ok(Err(e)) => source_error_response(e),
+        Err(e) => HttpResponse::InternalServerError().body(format!("Tag lookup failed: {}", e))
     }
 }
 
@@ -86,18 +177,20 @@ pub async fn change_wallpaper(params: web::Query<ChangeWallpaperParams>) -> Resu
 {
     println!("Change wallpaper");
 
+    let options = match params.to_options() {
+        Ok(options) => options,
+        Err(e) => return Ok(HttpResponse::BadRequest().body(e))
+    };
+
     let downloaded_images_folder = utils::get_downloads_directory();
 
     println!("Downloaded images folder: {}", downloaded_images_folder.to_str().unwrap());
 
-    let change_wallpaper_search_query: String = match &params.id {
+    let params = params.into_inner();
+    let id = match params.id {
         Some(id) => {
             println!("ID: {:?}", id);
-            format!(
-                "{}/{}",
-                utils::WALLHAVEN_DIRECT_ID,
-                id
-            )
+            id
         },
         None => {
             println!("Error: No ID provided.");
@@ -105,126 +198,652 @@ pub async fn change_wallpaper(params: web::Query<ChangeWallpaperParams>) -> Resu
         }
     };
 
-    // Get response back from API with query
-    let response = ureq::get(&change_wallpaper_search_query)
-        .header("User-Agent", format!("wallpaper_changer/{}", env!("CARGO_PKG_VERSION")))
-        .call()
-        .map_err(|e| actix_web::error::ErrorInternalServerError(e))?
-        .body_mut()
-        .read_json::<models::wallhaven::WHDirectModel>()
-        .map_err(|e| actix_web::error::ErrorInternalServerError(e))?;
+    let source = match sources::get_or_default(params.provider.as_deref()) {
+        Ok(source) => source,
+        Err(e) => return Ok(source_error_response(e))
+    };
 
-    match download::image::original(&response.data, &downloaded_images_folder.to_str().unwrap()) {
-        Ok(path) => {
-            println!("Downloaded image path: {}", path);
-            wallpaper::change(path.as_str()).unwrap();
-        },
-        Err(e) => println!("Error downloading image: {}", e)
-    }
+    // Get the image details from the provider
+    let (source, image) = match web::block(move || source.get_by_id(&id).map(|image| (source, image))).await? {
+        Ok(found) => found,
+        Err(e) => return Ok(source_error_response(e))
+    };
+
+    // Downloading and setting the wallpaper both block, so they run on the blocking thread pool too
+    web::block(move || -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let path = download::image::original(source.as_ref(), &image, downloaded_images_folder.to_str().unwrap())
+            .inspect_err(|e| println!("Error downloading image: {}", e))?;
+        println!("Downloaded image path: {}", path);
+        match &params.output {
+            Some(output) => wallpaper::change_for_output_with_options(output, path.as_str(), &options),
+            None => wallpaper::change_with_options(path.as_str(), &options)
+        }?;
+        Ok(())
+    }).await?.map_err(actix_web::error::ErrorInternalServerError)?;
 
     Ok(HttpResponse::Ok().finish())
 }
 
+// Maps collection errors onto the matching HTTP status
+fn collection_error_response(e: collections::CollectionError) -> HttpResponse {
+    match e {
+        collections::CollectionError::InvalidName(message) => HttpResponse::BadRequest().body(message),
+        collections::CollectionError::NotFound(message) => HttpResponse::NotFound().body(message),
+        collections::CollectionError::AlreadyExists(message) => HttpResponse::Conflict().body(message),
+        collections::CollectionError::InvalidArchive(message) => HttpResponse::BadRequest().body(message),
+        collections::CollectionError::Source(e) => source_error_response(e),
+        collections::CollectionError::Io(e) => HttpResponse::InternalServerError().body(format!("Collections error: {}", e)),
+    }
+}
+
+#[derive(Serialize)]
+struct TagResp { tag: String, path: String }
+
 #[derive(Deserialize)]
 pub struct CreateTagBody { pub name: String }
 
 #[post("/collections/tags")]
 pub async fn create_tag(body: web::Json<CreateTagBody>) -> impl Responder {
-    let tag = utils::sanitize_tag_name(&body.name);
-    if tag.is_empty() { return HttpResponse::BadRequest().body("Invalid tag name"); }
-    let collections_dir = utils::get_collections_directory();
-    if let Err(e) = utils::ensure_dir(&collections_dir) { return HttpResponse::InternalServerError().body(format!("Failed to init collections dir: {}", e)); }
-    let tag_dir = collections_dir.join(&tag);
-    match utils::ensure_dir(&tag_dir) {
-        Ok(_) => {
-            #[derive(Serialize)]
-            struct CreateTagResp { tag: String, path: String }
-            let resp = CreateTagResp { tag, path: tag_dir.to_string_lossy().to_string() };
-            HttpResponse::Ok().json(resp)
-        },
-        Err(e) => HttpResponse::InternalServerError().body(format!("Failed to create tag: {}", e))
+    match collections::create(&body.name) {
+        Ok((tag, dir)) => HttpResponse::Ok().json(TagResp { tag, path: dir.to_string_lossy().to_string() }),
+        Err(e) => collection_error_response(e)
+    }
+}
+
+#[patch("/collections/tags/{tag}")]
+pub async fn rename_tag(path: web::Path<String>, body: web::Json<CreateTagBody>) -> impl Responder {
+    match collections::rename(&path, &body.name) {
+        Ok((tag, dir)) => HttpResponse::Ok().json(TagResp { tag, path: dir.to_string_lossy().to_string() }),
+        Err(e) => collection_error_response(e)
+    }
+}
+
+// Removes the tag, the images themselves stay in the downloads folder
+#[delete("/collections/tags/{tag}")]
+pub async fn delete_tag(path: web::Path<String>) -> impl Responder {
+    match collections::delete(&path) {
+        Ok(()) => HttpResponse::NoContent().finish(),
+        Err(e) => collection_error_response(e)
     }
 }
 
 #[derive(Deserialize)]
-pub struct TagImageBody { pub id: String, pub tag: String }
+pub struct TagImageBody {
+    pub id: String,
+    pub tag: String,
+    #[serde(default)]
+    pub provider: Option<String>,
+}
 
 #[post("/collections/tag-image")]
 pub async fn tag_image(body: web::Json<TagImageBody>) -> impl Responder {
-    let tag = utils::sanitize_tag_name(&body.tag);
-    if tag.is_empty() { return HttpResponse::BadRequest().body("Invalid tag name"); }
-
-    // Ensure tag folder exists
-    let collections_dir = utils::get_collections_directory();
-    if let Err(e) = utils::ensure_dir(&collections_dir) { return HttpResponse::InternalServerError().body(format!("Failed to init collections dir: {}", e)); }
-    let tag_dir = collections_dir.join(&tag);
-    if let Err(e) = utils::ensure_dir(&tag_dir) { return HttpResponse::InternalServerError().body(format!("Failed to create tag dir: {}", e)); }
-
-    // Fetch image metadata by ID
-    let change_wallpaper_search_query = format!("{}/{}", utils::WALLHAVEN_DIRECT_ID, body.id);
-    let direct = match ureq::get(&change_wallpaper_search_query)
-        .header("User-Agent", format!("wallpaper_changer/{}", env!("CARGO_PKG_VERSION")))
-        .call() {
-        Ok(mut resp) => {
-            match resp.body_mut().read_json::<models::wallhaven::WHDirectModel>() {
-                Ok(json) => json,
-                Err(e) => return HttpResponse::InternalServerError().body(format!("Failed to parse image info: {}", e))
+    let TagImageBody { id, tag: name, provider } = body.into_inner();
+    let tag = utils::sanitize_tag_name(&name);
+    // Downloads the original when it isn't there yet
+    let result = web::block(move || {
+        collections::add_image(&name, provider.as_deref().unwrap_or(sources::DEFAULT_SOURCE), &id)
+    }).await;
+    match result {
+        Ok(Ok((dest, link))) => {
+            #[derive(Serialize)]
+            struct TagImageResp { tag: String, copied_to: String, link: Option<collections::LinkKind> }
+            HttpResponse::Ok().json(TagImageResp { tag, copied_to: dest.to_string_lossy().to_string(), link })
+        },
+        Ok(Err(e)) => collection_error_response(e),
+        Err(e) => HttpResponse::InternalServerError().body(format!("Tagging failed: {}", e))
+    }
+}
+
+#[delete("/collections/tags/{tag}/images/{source}/{id}")]
+pub async fn untag_image(path: web::Path<(String, String, String)>) -> impl Responder {
+    let (tag, source, id) = path.into_inner();
+    match collections::remove_image(&tag, &source, &id) {
+        Ok(()) => HttpResponse::NoContent().finish(),
+        Err(e) => collection_error_response(e)
+    }
+}
+
+#[derive(Deserialize)]
+pub struct TagMembershipBody {
+    #[serde(default)]
+    pub add: Vec<collections::ImageRef>,
+    #[serde(default)]
+    pub remove: Vec<collections::ImageRef>,
+}
+
+#[derive(Serialize)]
+struct TagMembershipFailure { source: String, id: String, error: String }
+
+#[derive(Serialize)]
+struct TagMembershipResp { added: Vec<collections::ImageRef>, removed: Vec<collections::ImageRef>, failed: Vec<TagMembershipFailure> }
+
+// Adds and removes several images at once, failures are reported per image
+#[patch("/collections/tags/{tag}/images")]
+pub async fn update_tag_images(path: web::Path<String>, body: web::Json<TagMembershipBody>) -> impl Responder {
+    let tag = path.into_inner();
+    if let Err(e) = collections::sanitize(&tag) {
+        return collection_error_response(e);
+    }
+
+    let body = body.into_inner();
+    // Adding downloads every image that isn't there yet, one after the other
+    let result = web::block(move || {
+        let mut resp = TagMembershipResp { added: Vec::new(), removed: Vec::new(), failed: Vec::new() };
+        for image in body.add {
+            match collections::add_image(&tag, &image.source, &image.id) {
+                Ok(_) => resp.added.push(image),
+                Err(e) => resp.failed.push(TagMembershipFailure { source: image.source, id: image.id, error: e.to_string() })
+            }
+        }
+        for image in body.remove {
+            match collections::remove_image(&tag, &image.source, &image.id) {
+                Ok(()) => resp.removed.push(image),
+                Err(e) => resp.failed.push(TagMembershipFailure { source: image.source, id: image.id, error: e.to_string() })
             }
+        }
+        resp
+    }).await;
+    match result {
+        Ok(resp) => HttpResponse::Ok().json(resp),
+        Err(e) => HttpResponse::InternalServerError().body(format!("Updating the tag failed: {}", e))
+    }
+}
+
+#[derive(Serialize)]
+pub struct CollectionsResp { pub tags: Vec<collections::CollectionItem> }
+
+#[get("/collections")]
+pub async fn list_collections() -> impl Responder {
+    match collections::list() {
+        Ok(tags) => HttpResponse::Ok().json(CollectionsResp { tags }),
+        Err(e) => HttpResponse::InternalServerError().body(format!("Failed to read collections: {}", e))
+    }
+}
+
+#[derive(Deserialize)]
+struct WallhavenCollectionsParams {
+    // Without a username the API key owner's collections are listed, private ones included
+    username: Option<String>,
+}
+
+#[get("/wallhaven/collections")]
+pub async fn list_wallhaven_collections(params: web::Query<WallhavenCollectionsParams>) -> impl Responder {
+    let username = params.into_inner().username.filter(|username| !username.trim().is_empty());
+    if username.is_none() && config::load().wallhaven_api_key.is_none() {
+        return HttpResponse::BadRequest().body("Pass a username or set a Wallhaven API key to list your own collections");
+    }
+    match web::block(move || sources::wallhaven::WallhavenSource::collections(username.as_deref())).await {
+        Ok(Ok(collections)) => HttpResponse::Ok().json(collections),
+        Ok(Err(e)) => source_error_response(e),
+        Err(e) => HttpResponse::InternalServerError().body(format!("Listing collections failed: {}", e))
+    }
+}
+
+#[derive(Deserialize)]
+struct SyncTagBody {
+    // Both set links the tag to a collection, both left out re-syncs the linked one
+    username: Option<String>,
+    collection_id: Option<u64>,
+}
+
+#[derive(Serialize)]
+struct SyncStartedResp { tag: String, status_url: String }
+
+// Syncing can take minutes, so it runs in the background: 202 with the URL to poll for the report
+#[post("/collections/tags/{tag}/sync")]
+pub async fn sync_tag(path: web::Path<String>, body: web::Json<SyncTagBody>) -> impl Responder {
+    let SyncTagBody { username, collection_id } = body.into_inner();
+    let collection = match (username, collection_id) {
+        (Some(username), Some(collection_id)) => Some((username, collection_id)),
+        (None, None) => None,
+        _ => return HttpResponse::BadRequest().body("username and collection_id go together")
+    };
+    match collections::sync::start(&path, collection) {
+        Ok(tag) => {
+            let status_url = format!("/collections/tags/{}/sync", tag);
+            HttpResponse::Accepted().insert_header(("Location", status_url.clone())).json(SyncStartedResp { tag, status_url })
         },
-        Err(e) => return HttpResponse::InternalServerError().body(format!("Failed to fetch image info: {}", e))
+        Err(e) => collection_error_response(e)
+    }
+}
+
+#[get("/collections/tags/{tag}/sync")]
+pub async fn sync_status(path: web::Path<String>) -> impl Responder {
+    match collections::sync::status(&path) {
+        Ok(Some(status)) => HttpResponse::Ok().json(status),
+        Ok(None) => HttpResponse::NotFound().body(format!("No sync of '{}' was started since the server started", path)),
+        Err(e) => collection_error_response(e)
+    }
+}
+
+#[delete("/collections/tags/{tag}/sync")]
+pub async fn unsync_tag(path: web::Path<String>) -> impl Responder {
+    match collections::sync::unlink(&path) {
+        Ok(Some(link)) => HttpResponse::Ok().json(link),
+        Ok(None) => HttpResponse::NotFound().body(format!("Tag '{}' is not synced with a Wallhaven collection", path)),
+        Err(e) => collection_error_response(e)
+    }
+}
+
+#[derive(Serialize)]
+struct SyncAllResp { queued: Vec<String> }
+
+// Re-syncs every linked tag in the background, one failing collection doesn't stop the others
+#[post("/collections/sync")]
+pub async fn sync_all_tags() -> impl Responder {
+    HttpResponse::Accepted().json(SyncAllResp { queued: collections::sync::start_all() })
+}
+
+// Status of every sync started since the server came up, by tag
+#[get("/collections/sync")]
+pub async fn sync_all_status() -> impl Responder {
+    HttpResponse::Ok().json(collections::sync::statuses())
+}
+
+#[derive(Deserialize)]
+struct ExportParams {
+    // false exports only the manifest, the importer then downloads the images by id
+    #[serde(default = "default_true")]
+    images: bool,
+}
+
+fn default_true() -> bool { true }
+
+// Streams an exported archive from its temporary file, which is removed once the response is done
+struct ExportBody {
+    file: tokio::fs::File,
+    path: PathBuf,
+    size: u64,
+}
+
+impl MessageBody for ExportBody {
+    type Error = std::io::Error;
+
+    fn size(&self) -> BodySize {
+        BodySize::Sized(self.size)
+    }
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Result<web::Bytes, Self::Error>>> {
+        let mut chunk = vec![0u8; 64 * 1024];
+        let mut buf = ReadBuf::new(&mut chunk);
+        match Pin::new(&mut self.get_mut().file).poll_read(cx, &mut buf) {
+            Poll::Ready(Ok(())) if buf.filled().is_empty() => Poll::Ready(None),
+            Poll::Ready(Ok(())) => Poll::Ready(Some(Ok(web::Bytes::copy_from_slice(buf.filled())))),
+            Poll::Ready(Err(e)) => Poll::Ready(Some(Err(e))),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl Drop for ExportBody {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_file(&self.path) {
+            eprintln!("Failed to remove {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+#[get("/collections/{tag}/export")]
+pub async fn export_tag(path: web::Path<String>, params: web::Query<ExportParams>) -> impl Responder {
+    let tag = path.into_inner();
+    let file_name = format!("{}.zip", collections::sanitize(&tag).unwrap_or_default().replace('"', "_"));
+    let include_images = params.images;
+    let archive = match web::block(move || collections::archive::export(&tag, include_images)).await {
+        Ok(Ok(archive)) => archive,
+        Ok(Err(e)) => return collection_error_response(e),
+        Err(e) => return HttpResponse::InternalServerError().body(format!("Export failed: {}", e))
+    };
+    let body = match tokio::fs::File::open(&archive).await {
+        Ok(file) => match file.metadata().await {
+            Ok(metadata) => ExportBody { file, path: archive, size: metadata.len() },
+            Err(e) => {
+                let _ = std::fs::remove_file(&archive);
+                return HttpResponse::InternalServerError().body(format!("Export failed: {}", e));
+            }
+        },
+        Err(e) => {
+            let _ = std::fs::remove_file(&archive);
+            return HttpResponse::InternalServerError().body(format!("Export failed: {}", e));
+        }
     };
+    HttpResponse::Ok()
+        .content_type("application/zip")
+        .insert_header(("Content-Disposition", format!("attachment; filename=\"{}\"", file_name)))
+        .body(body)
+}
+
+#[derive(Deserialize)]
+struct ImportParams {
+    // Defaults to the tag name stored in the archive
+    tag: Option<String>,
+    // Download missing images from their provider instead of using the bytes in the archive
+    #[serde(default)]
+    refetch: bool,
+}
 
-    // Ensure image downloaded to downloads directory
-    let downloads = utils::get_downloads_directory();
-    if let Err(e) = utils::ensure_dir(&downloads) { return HttpResponse::InternalServerError().body(format!("Failed to init downloads dir: {}", e)); }
-    let image_path = match download::image::original(&direct.data, downloads.to_str().unwrap()) {
-        Ok(p) => p,
-        Err(e) => return HttpResponse::InternalServerError().body(format!("Failed to download image: {}", e))
+// Archives can be large, so they are written to a temporary file rather than kept in memory
+async fn receive_archive(payload: &mut web::Payload, path: &Path) -> Result<(), HttpResponse> {
+    let write_error = |e: std::io::Error| HttpResponse::InternalServerError().body(format!("Failed to store the archive: {}", e));
+    let mut file = tokio::fs::File::create(path).await.map_err(write_error)?;
+    let mut size: u64 = 0;
+    while let Some(chunk) = payload.next().await {
+        let chunk = chunk.map_err(|e| HttpResponse::BadRequest().body(format!("Failed to read the archive: {}", e)))?;
+        size += chunk.len() as u64;
+        if size > collections::archive::MAX_ARCHIVE_SIZE {
+            return Err(HttpResponse::PayloadTooLarge().body(format!(
+                "Archives are limited to {} MiB", collections::archive::MAX_ARCHIVE_SIZE / 1024 / 1024
+            )));
+        }
+        file.write_all(&chunk).await.map_err(write_error)?;
+    }
+    file.flush().await.map_err(write_error)
+}
+
+#[post("/collections/import")]
+pub async fn import_tag(params: web::Query<ImportParams>, mut payload: web::Payload) -> impl Responder {
+    let ImportParams { tag, refetch } = params.into_inner();
+    let path = collections::archive::temp_path("import");
+    if let Err(resp) = receive_archive(&mut payload, &path).await {
+        let _ = std::fs::remove_file(&path);
+        return resp;
+    }
+    // Unpacking and refetching both block, so keep them off the worker
+    let result = web::block(move || {
+        let result = std::fs::File::open(&path)
+            .map_err(collections::CollectionError::from)
+            .and_then(|file| collections::archive::import(std::io::BufReader::new(file), tag.as_deref(), refetch));
+        if let Err(e) = std::fs::remove_file(&path) {
+            eprintln!("Failed to remove {}: {}", path.display(), e);
+        }
+        result
+    }).await;
+    match result {
+        Ok(Ok(report)) => HttpResponse::Ok().json(report),
+        Ok(Err(e)) => collection_error_response(e),
+        Err(e) => HttpResponse::InternalServerError().body(format!("Import failed: {}", e))
+    }
+}
+
+#[derive(Serialize)]
+pub struct AccountResp {
+    pub has_api_key: bool,
+    pub api_key_hint: Option<String>,
+    pub settings: Option<models::wallhaven::WHUserSettings>,
+    pub error: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct UpdateAccountBody { pub api_key: Option<String> }
+
+// Only show the last 4 characters of the key back to the app
+fn api_key_hint(key: &str) -> String {
+    let visible: String = key.chars().rev().take(4).collect::<Vec<char>>().into_iter().rev().collect();
+    format!("****{}", visible)
+}
+
+#[get("/account")]
+pub async fn get_account() -> impl Responder {
+    let api_key = config::load().wallhaven_api_key.filter(|k| !k.trim().is_empty());
+    let Some(key) = api_key else {
+        return HttpResponse::Ok().json(AccountResp { has_api_key: false, api_key_hint: None, settings: None, error: None });
+    };
+    let api_key_hint = Some(api_key_hint(&key));
+    let (settings, error) = match web::block(move || utils::get_wallhaven_settings(&key)).await {
+        Ok(Ok(settings)) => (Some(settings), None),
+        Ok(Err(e)) => (None, Some(format!("Failed to fetch Wallhaven settings: {}", e))),
+        Err(e) => (None, Some(format!("Failed to fetch Wallhaven settings: {}", e)))
+    };
+    HttpResponse::Ok().json(AccountResp { has_api_key: true, api_key_hint, settings, error })
+}
+
+#[put("/account")]
+pub async fn update_account(body: web::Json<UpdateAccountBody>) -> impl Responder {
+    // An empty or missing key clears the stored one
+    let api_key = body.api_key.as_deref().map(str::trim).filter(|k| !k.is_empty()).map(str::to_string);
+
+    // Check the key against Wallhaven before storing it
+    let settings = match api_key.clone() {
+        Some(key) => match web::block(move || utils::get_wallhaven_settings(&key)).await {
+            Ok(Ok(settings)) => Some(settings),
+            Ok(Err(e)) => return HttpResponse::BadRequest().body(format!("Invalid Wallhaven API key: {}", e)),
+            Err(e) => return HttpResponse::InternalServerError().body(format!("Failed to check the API key: {}", e))
+        },
+        None => None
     };
 
-    // Copy into tag folder
-    let src = PathBuf::from(&image_path);
-    let file_name = src.file_name().unwrap_or_default();
-    let dest = tag_dir.join(file_name);
-    if !dest.exists() {
-        if let Err(e) = fs::copy(&src, &dest) { return HttpResponse::InternalServerError().body(format!("Failed to copy image: {}", e)); }
+    if let Err(e) = config::update(|c| c.wallhaven_api_key = api_key.clone()) {
+        return HttpResponse::InternalServerError().body(format!("Failed to save config: {}", e));
     }
 
-    #[derive(Serialize)]
-    struct TagImageResp { tag: String, copied_to: String }
-    let resp = TagImageResp { tag, copied_to: dest.to_string_lossy().to_string() };
+    let resp = AccountResp {
+        has_api_key: api_key.is_some(),
+        api_key_hint: api_key.as_deref().map(api_key_hint),
+        settings,
+        error: None,
+    };
     HttpResponse::Ok().json(resp)
 }
 
+#[post("/rotation/start")]
+pub async fn start_rotation(scheduler: web::Data<scheduler::Scheduler>, body: web::Json<scheduler::RotationSettings>) -> impl Responder {
+    match scheduler.start(body.into_inner()) {
+        Ok(status) => HttpResponse::Ok().json(status),
+        Err(e) => HttpResponse::BadRequest().body(e)
+    }
+}
+
+#[post("/rotation/stop")]
+pub async fn stop_rotation(scheduler: web::Data<scheduler::Scheduler>) -> impl Responder {
+    HttpResponse::Ok().json(scheduler.stop())
+}
+
+#[get("/rotation/status")]
+pub async fn rotation_status(scheduler: web::Data<scheduler::Scheduler>) -> impl Responder {
+    HttpResponse::Ok().json(scheduler.status())
+}
+
+#[get("/monitors")]
+pub async fn list_monitors() -> impl Responder {
+    match wallpaper::list_monitors() {
+        Ok(monitors) => HttpResponse::Ok().json(monitors),
+        Err(e) => HttpResponse::InternalServerError().body(format!("Failed to list monitors: {}", e))
+    }
+}
+
+#[derive(Deserialize)]
+pub struct DailyParams { provider: Option<String> }
+
 #[derive(Serialize)]
-pub struct CollectionItem { pub name: String, pub images: Vec<String> }
+pub struct DailyResp {
+    pub changed: bool,
+    pub image: Option<daily::DailyImage>,
+    pub message: Option<String>,
+}
+
+#[post("/daily")]
+pub async fn daily_wallpaper(params: web::Query<DailyParams>) -> impl Responder {
+    let providers = match daily::parse_providers(params.provider.as_deref()) {
+        Ok(providers) => providers,
+        Err(e) => return HttpResponse::BadRequest().body(e)
+    };
+
+    // Fetching, downloading and setting the image all block
+    match web::block(move || daily::apply(&providers)).await {
+        Ok(Ok(Some(image))) => HttpResponse::Ok().json(DailyResp { changed: true, image: Some(image), message: None }),
+        // e.g. APOD is a video today
+        Ok(Ok(None)) => HttpResponse::Ok().json(DailyResp {
+            changed: false,
+            image: None,
+            message: Some("No daily image available today".to_string()),
+        }),
+        Ok(Err(e)) => HttpResponse::InternalServerError().body(format!("Failed to set daily wallpaper: {}", e)),
+        Err(e) => HttpResponse::InternalServerError().body(format!("Failed to set daily wallpaper: {}", e))
+    }
+}
+
+// Searches the metadata of downloaded images, no network involved
+#[get("/library")]
+pub async fn search_library(query: web::Query<library::LibraryQuery>) -> impl Responder {
+    match query.run() {
+        Ok(page) => HttpResponse::Ok().json(page),
+        Err(e) => HttpResponse::BadRequest().body(e)
+    }
+}
+
+#[get("/library/{source}/{id}")]
+pub async fn get_library_entry(path: web::Path<(String, String)>) -> impl Responder {
+    let (source, id) = path.into_inner();
+    match library::get(&source, &id) {
+        Some(entry) => HttpResponse::Ok().json(entry),
+        None => HttpResponse::NotFound().body(format!("No downloaded image {}", library::key(&source, &id)))
+    }
+}
+
+#[get("/history")]
+pub async fn list_history(query: web::Query<history::HistoryQuery>) -> impl Responder {
+    match query.run() {
+        Ok(page) => HttpResponse::Ok().json(page),
+        Err(e) => HttpResponse::BadRequest().body(e)
+    }
+}
+
+#[post("/history/previous")]
+pub async fn previous_wallpaper() -> impl Responder {
+    match web::block(|| history::step(history::Direction::Previous)).await {
+        Ok(Ok(Some(entry))) => HttpResponse::Ok().json(entry),
+        Ok(Ok(None)) => HttpResponse::BadRequest().body("Already at the oldest wallpaper in history"),
+        Ok(Err(e)) => HttpResponse::InternalServerError().body(e),
+        Err(e) => HttpResponse::InternalServerError().body(format!("Failed to set wallpaper: {}", e))
+    }
+}
+
+#[post("/history/next")]
+pub async fn next_wallpaper() -> impl Responder {
+    match web::block(|| history::step(history::Direction::Next)).await {
+        Ok(Ok(Some(entry))) => HttpResponse::Ok().json(entry),
+        Ok(Ok(None)) => HttpResponse::BadRequest().body("Already at the latest wallpaper in history"),
+        Ok(Err(e)) => HttpResponse::InternalServerError().body(e),
+        Err(e) => HttpResponse::InternalServerError().body(format!("Failed to set wallpaper: {}", e))
+    }
+}
 
 #[derive(Serialize)]
-pub struct CollectionsResp { pub tags: Vec<CollectionItem> }
+pub struct CurrentResp {
+    #[serde(flatten)]
+    pub state: state::CurrentState,
+    // Library entry for the image when it was downloaded through the app
+    pub image: Option<library::LibraryEntry>,
+    // What the desktop reports right now, None if the backend can't tell
+    pub desktop: Option<String>,
+    pub changed_externally: bool,
+}
 
-#[get("/collections")]
-pub async fn list_collections() -> impl Responder {
-    let collections_dir = utils::get_collections_directory();
-    if let Err(e) = utils::ensure_dir(&collections_dir) { return HttpResponse::InternalServerError().body(format!("Failed to init collections dir: {}", e)); }
-    let mut items: Vec<CollectionItem> = Vec::new();
-    let read_dir = match fs::read_dir(&collections_dir) { Ok(rd) => rd, Err(e) => return HttpResponse::InternalServerError().body(format!("Failed to read collections: {}", e)) };
-    for entry in read_dir.flatten() {
-        let path = entry.path();
-        if path.is_dir() {
-            let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
-            let mut images: Vec<String> = Vec::new();
-            if let Ok(imgs) = fs::read_dir(&path) {
-                for img in imgs.flatten() {
-                    let p = img.path();
-                    if p.is_file() {
-                        let s = p.to_string_lossy().to_string();
-                        images.push(s);
-                    }
-                }
-            }
-            items.push(CollectionItem { name, images });
+#[get("/current")]
+pub async fn current_wallpaper() -> impl Responder {
+    let state = state::load();
+    let image = state.path.as_deref().and_then(library::find_by_path);
+    // The backends ask the desktop through commands like gsettings
+    let desktop = match web::block(wallpaper::current).await {
+        Ok(Ok(desktop)) => desktop,
+        Ok(Err(e)) => {
+            eprintln!("Could not read the current wallpaper: {}", e);
+            None
+        },
+        Err(e) => {
+            eprintln!("Could not read the current wallpaper: {}", e);
+            None
         }
+    };
+    let changed_externally = state.external_change.is_some();
+    HttpResponse::Ok().json(CurrentResp { state, image, desktop, changed_externally })
+}
+
+// Images have to be in the library to be favourited or rated, download them first if they aren't
+async fn ensure_in_library(source_name: &str, id: &str) -> Result<(), HttpResponse> {
+    if library::get(source_name, id).is_some() {
+        return Ok(());
+    }
+    let (source_name, id) = (source_name.to_string(), id.to_string());
+    let result = web::block(move || -> Result<(), SourceError> {
+        let source = sources::get_or_default(Some(&source_name))?;
+        let image = source.get_by_id(&id)?;
+        let downloads = utils::get_downloads_directory();
+        download::image::original(source.as_ref(), &image, downloads.to_str().unwrap())?;
+        Ok(())
+    }).await;
+    match result {
+        Ok(Ok(())) => Ok(()),
+        Ok(Err(e)) => Err(source_error_response(e)),
+        Err(e) => Err(HttpResponse::InternalServerError().body(format!("Failed to download image: {}", e)))
+    }
+}
+
+#[derive(Deserialize)]
+pub struct FavoriteBody { pub favorite: bool }
+
+#[put("/library/{source}/{id}/favorite")]
+pub async fn set_favorite(path: web::Path<(String, String)>, body: web::Json<FavoriteBody>) -> impl Responder {
+    let (source, id) = path.into_inner();
+    if let Err(resp) = ensure_in_library(&source, &id).await {
+        return resp;
+    }
+    match library::set_favorite(&source, &id, body.favorite) {
+        Ok(Some(entry)) => HttpResponse::Ok().json(entry),
+        Ok(None) => HttpResponse::NotFound().body(format!("No downloaded image {}", library::key(&source, &id))),
+        Err(e) => HttpResponse::InternalServerError().body(format!("Failed to save library: {}", e))
+    }
+}
+
+#[derive(Deserialize)]
+pub struct RatingBody { pub rating: Option<u8> }
+
+#[put("/library/{source}/{id}/rating")]
+pub async fn set_rating(path: web::Path<(String, String)>, body: web::Json<RatingBody>) -> impl Responder {
+    let (source, id) = path.into_inner();
+    if let Some(rating) = body.rating
+        && !(1..=5).contains(&rating) {
+        return HttpResponse::BadRequest().body(format!("Invalid rating '{}', expected 1-5 or null to clear it", rating));
+    }
+    if let Err(resp) = ensure_in_library(&source, &id).await {
+        return resp;
+    }
+    match library::set_rating(&source, &id, body.rating) {
+        Ok(Some(entry)) => HttpResponse::Ok().json(entry),
+        Ok(None) => HttpResponse::NotFound().body(format!("No downloaded image {}", library::key(&source, &id))),
+        Err(e) => HttpResponse::InternalServerError().body(format!("Failed to save library: {}", e))
+    }
+}
+
+// Same filters as /library, limited to favourites
+#[get("/favorites")]
+pub async fn list_favorites(query: web::Query<library::LibraryQuery>) -> impl Responder {
+    let query = library::LibraryQuery { favorite: Some(true), ..query.into_inner() };
+    match query.run() {
+        Ok(page) => HttpResponse::Ok().json(page),
+        Err(e) => HttpResponse::BadRequest().body(e)
+    }
+}
+
+// "Surprise me", sets a random downloaded image, favourites and well rated ones are more likely
+#[post("/surprise")]
+pub async fn surprise_me(query: web::Query<library::LibraryQuery>) -> impl Responder {
+    let query = query.into_inner();
+    // Setting the wallpaper runs desktop commands
+    let result = web::block(move || -> std::io::Result<Option<library::LibraryEntry>> {
+        let entries: Vec<library::LibraryEntry> = query.matching().into_iter()
+            .filter(|entry| Path::new(&entry.path).is_file())
+            .collect();
+        let Some(entry) = library::pick_weighted(entries) else {
+            return Ok(None);
+        };
+        wallpaper::change(&entry.path)?;
+        Ok(Some(entry))
+    }).await;
+
+    match result {
+        Ok(Ok(Some(entry))) => HttpResponse::Ok().json(entry),
+        Ok(Ok(None)) => HttpResponse::NotFound().body("No downloaded images match, download or favourite some first"),
+        Ok(Err(e)) => HttpResponse::InternalServerError().body(format!("Failed to set wallpaper: {}", e)),
+        Err(e) => HttpResponse::InternalServerError().body(format!("Failed to set wallpaper: {}", e))
     }
-    let resp = CollectionsResp { tags: items };
-    HttpResponse::Ok().json(resp)
 }
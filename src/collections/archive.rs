@@ -0,0 +1,439 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io::{self, BufWriter, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use serde::{Deserialize, Serialize};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+use crate::models::wallpaper::Wallpaper;
+use crate::library::{self, Library};
+use crate::{scheduler, sources, utils};
+use super::{CollectionError, ImageFailure, ImageRef};
+
+// Tags are shared as zip files holding manifest.json and, unless left out, the images under images/
+// The manifest carries the library metadata so the images keep their attribution on the other side
+
+const MANIFEST_NAME: &str = "manifest.json";
+const IMAGES_DIR: &str = "images";
+const MANIFEST_VERSION: u32 = 1;
+pub const MAX_ARCHIVE_SIZE: u64 = 1024 * 1024 * 1024;
+// Limits for what a single entry unpacks to, the sizes in the zip headers can't be trusted
+const MAX_IMAGE_BYTES: u64 = 256 * 1024 * 1024;
+const MAX_MANIFEST_BYTES: u64 = 16 * 1024 * 1024;
+
+// Keeps the names of concurrent exports and imports apart
+static NEXT_TEMP: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Manifest {
+    pub version: u32,
+    pub tag: String,
+    pub exported_at: u64,
+    pub images: Vec<ManifestImage>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ManifestImage {
+    // Source, id, tags and attribution (author, source_url) come from the library entry
+    #[serde(flatten)]
+    pub wallpaper: Wallpaper,
+    // Path inside the archive, None when only the manifest was exported
+    #[serde(default)]
+    pub file: Option<String>,
+}
+
+#[derive(Debug, Serialize, Default)]
+pub struct ImportReport {
+    pub tag: String,
+    // Written from the archive into the downloads folder
+    pub imported: Vec<ImageRef>,
+    // Already downloaded, only linked into the tag
+    pub existing: Vec<ImageRef>,
+    // Downloaded again from the provider
+    pub refetched: Vec<ImageRef>,
+    pub failed: Vec<ImageFailure>,
+}
+
+fn archive_error(e: zip::result::ZipError) -> CollectionError {
+    match e {
+        zip::result::ZipError::Io(e) => CollectionError::Io(e),
+        e => CollectionError::InvalidArchive(format!("Invalid archive: {}", e)),
+    }
+}
+
+// Images that were never recorded in the library (e.g. tagged before it existed) get what the file itself tells
+fn wallpaper_for(known: &Library, image: &ImageRef, path: &Path) -> Wallpaper {
+    if let Some(entry) = known.entries.get(&library::key(&image.source, &image.id)) {
+        return entry.wallpaper.clone();
+    }
+    let (width, height) = image::image_dimensions(path).unwrap_or((0, 0));
+    let file_type = image::ImageFormat::from_path(path)
+        .map(|format| format.to_mime_type().to_string())
+        .unwrap_or_default();
+    Wallpaper {
+        source: image.source.clone(),
+        id: image.id.clone(),
+        url: String::new(),
+        thumbnail_url: String::new(),
+        original_url: String::new(),
+        width,
+        height,
+        file_type,
+        file_size: fs::metadata(path).ok().map(|m| m.len()),
+        author: None,
+        source_url: None,
+        tags: Vec::new(),
+        colors: Vec::new(),
+        purity: None,
+        category: None,
+        created_at: None,
+    }
+}
+
+// Where an archive is kept while it is sent or received, removed again once that's done
+pub fn temp_path(kind: &str) -> PathBuf {
+    env::temp_dir().join(format!(
+        "wallpaper_changer-{}-{}-{}.zip",
+        kind,
+        process::id(),
+        NEXT_TEMP.fetch_add(1, Ordering::Relaxed)
+    ))
+}
+
+// Writes the zip to a temporary file, the caller sends it and removes it afterwards
+pub fn export(name: &str, include_images: bool) -> Result<PathBuf, CollectionError> {
+    let (tag, dir) = super::existing_tag_dir(name)?;
+    let mut images: Vec<PathBuf> = fs::read_dir(&dir)?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file())
+        .collect();
+    images.sort();
+
+    let path = temp_path("export");
+    match write_zip(&path, tag, images, include_images) {
+        Ok(()) => Ok(path),
+        Err(e) => {
+            let _ = fs::remove_file(&path);
+            Err(e)
+        }
+    }
+}
+
+fn write_zip(path: &Path, tag: String, images: Vec<PathBuf>, include_images: bool) -> Result<(), CollectionError> {
+    let mut writer = ZipWriter::new(BufWriter::new(fs::File::create(path)?));
+    // Images are already compressed, deflating them again only costs time
+    let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+    let mut manifest = Manifest { version: MANIFEST_VERSION, tag, exported_at: scheduler::now(), images: Vec::new() };
+    let known = library::load();
+
+    for path in images {
+        let Some(image) = super::image_ref(&path) else {
+            eprintln!("Skipping {}, not named <source>-<id>", path.display());
+            continue;
+        };
+        let file = if include_images {
+            let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+            let file = format!("{}/{}", IMAGES_DIR, file_name);
+            writer.start_file(file.as_str(), stored).map_err(archive_error)?;
+            // Follows symlinked tag entries to the downloaded file
+            io::copy(&mut fs::File::open(&path)?, &mut writer)?;
+            Some(file)
+        } else {
+            None
+        };
+        manifest.images.push(ManifestImage { wallpaper: wallpaper_for(&known, &image, &path), file });
+    }
+
+    writer.start_file(MANIFEST_NAME, SimpleFileOptions::default()).map_err(archive_error)?;
+    let json = serde_json::to_vec_pretty(&manifest).map_err(io::Error::other)?;
+    writer.write_all(&json)?;
+    writer.finish().map_err(archive_error)?
+        .into_inner().map_err(|e| e.into_error())?;
+    Ok(())
+}
+
+// Ids end up in file names, so anything that could escape the downloads folder is refused
+fn valid_image(image: &ImageRef) -> Result<(), String> {
+    if !sources::SOURCE_NAMES.contains(&image.source.as_str()) {
+        return Err(format!("Unknown provider '{}'", image.source));
+    }
+    if image.id.is_empty() || !image.id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return Err(format!("Invalid id '{}'", image.id));
+    }
+    Ok(())
+}
+
+// The manifest comes from someone else, remote images may only point at http(s) URLs and the file:// URLs
+// of local images are paths on the exporting machine, so they are replaced by the imported file
+fn check_urls(wallpaper: &mut Wallpaper) -> Result<(), String> {
+    let urls = [&mut wallpaper.url, &mut wallpaper.thumbnail_url, &mut wallpaper.original_url];
+    if wallpaper.source == "local" {
+        urls.into_iter().for_each(String::clear);
+        return Ok(());
+    }
+    match urls.into_iter().find(|url| !url.is_empty() && !url.starts_with("https://") && !url.starts_with("http://")) {
+        Some(url) => Err(format!("Invalid URL '{}'", url)),
+        None => Ok(()),
+    }
+}
+
+// What goes into the library for an imported file, local images now point at their copy on this machine
+fn library_entry(wallpaper: &Wallpaper, path: &Path) -> (Wallpaper, String) {
+    let mut wallpaper = wallpaper.clone();
+    if wallpaper.source == "local" {
+        let url = sources::local::file_url(path);
+        wallpaper.url = url.clone();
+        wallpaper.thumbnail_url = url.clone();
+        wallpaper.original_url = url;
+    }
+    (wallpaper, path.to_string_lossy().to_string())
+}
+
+// Files in the downloads folder by their "<source>-<id>" stem
+fn downloads_by_stem(downloads: &Path) -> io::Result<HashMap<String, PathBuf>> {
+    Ok(fs::read_dir(downloads)?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file())
+        .filter_map(|path| Some((path.file_stem()?.to_str()?.to_string(), path)))
+        .collect())
+}
+
+// An earlier download of the same image, found through the library or by its file name
+fn existing_download(known: &Library, downloaded: &HashMap<String, PathBuf>, image: &ImageRef) -> Option<PathBuf> {
+    if let Some(entry) = known.entries.get(&library::key(&image.source, &image.id))
+        && Path::new(&entry.path).is_file() {
+        return Some(PathBuf::from(&entry.path));
+    }
+    downloaded.get(&format!("{}-{}", image.source, image.id)).cloned()
+}
+
+fn extract<R: Read + Seek>(archive: &mut ZipArchive<R>, file: &str, dest_stem: &Path, max_bytes: u64) -> Result<PathBuf, CollectionError> {
+    let mut entry = archive.by_name(file).map_err(archive_error)?;
+    let extension = Path::new(file).extension().and_then(|e| e.to_str()).unwrap_or("jpg").to_lowercase();
+    let dest = dest_stem.with_extension(&extension);
+    if !utils::is_image(&dest) {
+        return Err(CollectionError::InvalidArchive(format!("{} is not an image", file)));
+    }
+    let temp = dest.with_extension(format!("{}.part", extension));
+    let written = io::copy(&mut (&mut entry).take(max_bytes + 1), &mut fs::File::create(&temp)?);
+    match written {
+        Ok(written) if written <= max_bytes => {},
+        Ok(_) => {
+            fs::remove_file(&temp)?;
+            return Err(CollectionError::InvalidArchive(format!("{} is larger than {} MiB", file, max_bytes / 1024 / 1024)));
+        },
+        Err(e) => {
+            let _ = fs::remove_file(&temp);
+            return Err(e.into());
+        },
+    }
+    fs::rename(&temp, &dest)?;
+    Ok(dest)
+}
+
+// Unpacks an exported tag into the collections folder, defaulting to the tag name in the manifest
+// Images that were downloaded before are linked instead of written again, with refetch the archived bytes
+// are ignored and every missing image is downloaded from its provider by id
+fn read_manifest<R: Read + Seek>(archive: &mut ZipArchive<R>) -> Result<Manifest, CollectionError> {
+    let mut entry = archive.by_name(MANIFEST_NAME)
+        .map_err(|_| CollectionError::InvalidArchive(format!("The archive has no {}", MANIFEST_NAME)))?;
+    let mut json = Vec::new();
+    (&mut entry).take(MAX_MANIFEST_BYTES + 1).read_to_end(&mut json)?;
+    if json.len() as u64 > MAX_MANIFEST_BYTES {
+        return Err(CollectionError::InvalidArchive(format!("{} is larger than {} MiB", MANIFEST_NAME, MAX_MANIFEST_BYTES / 1024 / 1024)));
+    }
+    let manifest: Manifest = serde_json::from_slice(&json)
+        .map_err(|e| CollectionError::InvalidArchive(format!("Invalid {}: {}", MANIFEST_NAME, e)))?;
+    if manifest.version > MANIFEST_VERSION {
+        return Err(CollectionError::InvalidArchive(format!("Unsupported manifest version {}", manifest.version)));
+    }
+    Ok(manifest)
+}
+
+pub fn import<R: Read + Seek>(reader: R, name: Option<&str>, refetch: bool) -> Result<ImportReport, CollectionError> {
+    let mut archive = ZipArchive::new(reader).map_err(archive_error)?;
+    let manifest = read_manifest(&mut archive)?;
+
+    let (tag, dir) = super::create(name.unwrap_or(&manifest.tag))?;
+    let downloads = utils::get_downloads_directory();
+    utils::ensure_dir(&downloads)?;
+    // Read once up front and written once at the end rather than for every image
+    let known = library::load();
+    let mut downloaded = downloads_by_stem(&downloads)?;
+    let mut recorded: Vec<(Wallpaper, String)> = Vec::new();
+
+    let mut report = ImportReport { tag: tag.clone(), ..Default::default() };
+    for mut item in manifest.images {
+        let image = ImageRef { source: item.wallpaper.source.clone(), id: item.wallpaper.id.clone() };
+        let fail = |error: String| ImageFailure { source: image.source.clone(), id: image.id.clone(), error };
+        if let Err(e) = valid_image(&image).and_then(|()| check_urls(&mut item.wallpaper)) {
+            report.failed.push(fail(e));
+            continue;
+        }
+
+        if let Some(path) = existing_download(&known, &downloaded, &image) {
+            if !known.entries.contains_key(&library::key(&image.source, &image.id)) {
+                recorded.push(library_entry(&item.wallpaper, &path));
+            }
+            match link_into(&path, &dir, &item.wallpaper) {
+                Ok(()) => report.existing.push(image),
+                Err(e) => report.failed.push(fail(e.to_string())),
+            }
+            continue;
+        }
+
+        match (&item.file, refetch) {
+            (Some(file), false) => {
+                let result = extract(&mut archive, file, &downloads.join(item.wallpaper.file_stem()), MAX_IMAGE_BYTES)
+                    .and_then(|path| {
+                        recorded.push(library_entry(&item.wallpaper, &path));
+                        downloaded.insert(item.wallpaper.file_stem(), path.clone());
+                        link_into(&path, &dir, &item.wallpaper)
+                    });
+                match result {
+                    Ok(()) => report.imported.push(image),
+                    Err(e) => report.failed.push(fail(e.to_string())),
+                }
+            },
+            _ => match super::add_image(&tag, &image.source, &image.id) {
+                Ok(_) => report.refetched.push(image),
+                Err(e) => report.failed.push(fail(e.to_string())),
+            },
+        }
+    }
+    if !recorded.is_empty()
+        && let Err(e) = library::record_all(&recorded) {
+        eprintln!("Failed to record the imported images in the library: {}", e);
+    }
+    Ok(report)
+}
+
+fn link_into(path: &Path, dir: &Path, wallpaper: &Wallpaper) -> Result<(), CollectionError> {
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("jpg");
+    let dest = dir.join(format!("{}.{}", wallpaper.file_stem(), extension));
+    if !dest.exists() {
+        super::link_or_copy(path, &dest)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use super::*;
+
+    fn zip(entries: &[(&str, &[u8])]) -> ZipArchive<Cursor<Vec<u8>>> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, bytes) in entries {
+            writer.start_file(*name, SimpleFileOptions::default()).unwrap();
+            writer.write_all(bytes).unwrap();
+        }
+        ZipArchive::new(writer.finish().unwrap()).unwrap()
+    }
+
+    fn wallpaper(source: &str, id: &str) -> Wallpaper {
+        Wallpaper {
+            source: source.to_string(),
+            id: id.to_string(),
+            url: format!("https://wallhaven.cc/w/{}", id),
+            thumbnail_url: format!("https://th.wallhaven.cc/small/{}.jpg", id),
+            original_url: format!("https://w.wallhaven.cc/full/{}.jpg", id),
+            width: 1920,
+            height: 1080,
+            file_type: "image/jpeg".to_string(),
+            file_size: None,
+            author: None,
+            source_url: None,
+            tags: Vec::new(),
+            colors: Vec::new(),
+            purity: None,
+            category: None,
+            created_at: None,
+        }
+    }
+
+    fn invalid_archive(result: Result<impl std::fmt::Debug, CollectionError>) -> String {
+        match result {
+            Err(CollectionError::InvalidArchive(message)) => message,
+            other => panic!("expected an invalid archive, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn manifest() {
+        let json = r#"{ "version": 1, "tag": "cats", "exported_at": 0, "images": [] }"#;
+        let manifest = read_manifest(&mut zip(&[(MANIFEST_NAME, json.as_bytes())])).unwrap();
+        assert_eq!(manifest.tag, "cats");
+
+        assert!(invalid_archive(read_manifest(&mut zip(&[("images/a.jpg", b"")]))).contains("no manifest.json"));
+        assert!(invalid_archive(read_manifest(&mut zip(&[(MANIFEST_NAME, b"{")]))).starts_with("Invalid manifest.json"));
+        let newer = r#"{ "version": 2, "tag": "cats", "exported_at": 0, "images": [] }"#;
+        assert!(invalid_archive(read_manifest(&mut zip(&[(MANIFEST_NAME, newer.as_bytes())]))).contains("version 2"));
+    }
+
+    #[test]
+    fn manifest_size_is_capped() {
+        // Whitespace is valid JSON padding, only the size should make it fail
+        let mut json = vec![b' '; MAX_MANIFEST_BYTES as usize];
+        json.extend_from_slice(br#"{ "version": 1, "tag": "cats", "exported_at": 0, "images": [] }"#);
+        assert!(invalid_archive(read_manifest(&mut zip(&[(MANIFEST_NAME, &json)]))).contains("larger than"));
+    }
+
+    #[test]
+    fn entry_size_is_capped() {
+        let dir = env::temp_dir().join(format!("wallpaper_changer-extract-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let image = vec![7u8; 2048];
+        let mut archive = zip(&[("images/wallhaven-abc.JPG", &image), ("images/notes.txt", b"hi")]);
+
+        assert!(invalid_archive(extract(&mut archive, "images/wallhaven-abc.JPG", &dir.join("wallhaven-abc"), 1024)).contains("larger than"));
+        // Nothing is left behind, not even the partial file
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+
+        let path = extract(&mut archive, "images/wallhaven-abc.JPG", &dir.join("wallhaven-abc"), 2048).unwrap();
+        assert_eq!(path, dir.join("wallhaven-abc.jpg"));
+        assert_eq!(fs::read(&path).unwrap(), image);
+
+        assert!(invalid_archive(extract(&mut archive, "images/notes.txt", &dir.join("wallhaven-def"), 2048)).contains("not an image"));
+        assert!(invalid_archive(extract(&mut archive, "images/missing.jpg", &dir.join("wallhaven-ghi"), 2048)).starts_with("Invalid archive"));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn source_and_id() {
+        let image = |source: &str, id: &str| ImageRef { source: source.to_string(), id: id.to_string() };
+        assert!(valid_image(&image("wallhaven", "1pvwjw")).is_ok());
+        assert!(valid_image(&image("unsplash", "Dwu85P9-SO_k")).is_ok());
+        assert!(valid_image(&image("flickr", "123")).unwrap_err().contains("Unknown provider"));
+        assert!(valid_image(&image("wallhaven", "")).is_err());
+        assert!(valid_image(&image("wallhaven", "../../etc/passwd")).is_err());
+        assert!(valid_image(&image("wallhaven", "a/b")).is_err());
+        assert!(valid_image(&image("wallhaven", "a.b")).is_err());
+    }
+
+    #[test]
+    fn urls() {
+        let mut remote = wallpaper("wallhaven", "1pvwjw");
+        assert!(check_urls(&mut remote).is_ok());
+        remote.thumbnail_url.clear();
+        assert!(check_urls(&mut remote).is_ok());
+
+        for url in ["file:///etc/passwd", "javascript:alert(1)", "ftp://example.com/a.jpg", "/etc/passwd"] {
+            let mut wallpaper = wallpaper("wallhaven", "1pvwjw");
+            wallpaper.original_url = url.to_string();
+            assert!(check_urls(&mut wallpaper).unwrap_err().contains(url));
+        }
+
+        // Paths on the exporting machine are dropped, the import points them at its own copy
+        let mut local = wallpaper("local", "abc");
+        local.original_url = "file:///home/someone/Pictures/abc.jpg".to_string();
+        assert!(check_urls(&mut local).is_ok());
+        assert!(local.url.is_empty() && local.thumbnail_url.is_empty() && local.original_url.is_empty());
+    }
+}
@@ -0,0 +1,290 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use crate::models::wallpaper::Wallpaper;
+use crate::sources::{self, ImageSource, SourceError};
+use crate::{download, library, utils};
+
+pub mod archive;
+pub mod sync;
+
+// Tags are folders under the collections directory holding links to the downloaded images,
+// so tagging never duplicates the files and the folders stay browsable in a file manager
+// Files are named <source>-<id>.<ext> like in the downloads folder
+
+#[derive(Debug)]
+pub enum CollectionError {
+    InvalidName(String),
+    NotFound(String),
+    AlreadyExists(String),
+    InvalidArchive(String),
+    Source(SourceError),
+    Io(io::Error),
+}
+
+impl fmt::Display for CollectionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CollectionError::InvalidName(message) => write!(f, "{}", message),
+            CollectionError::NotFound(message) => write!(f, "{}", message),
+            CollectionError::AlreadyExists(message) => write!(f, "{}", message),
+            CollectionError::InvalidArchive(message) => write!(f, "{}", message),
+            CollectionError::Source(e) => write!(f, "{}", e),
+            CollectionError::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for CollectionError {}
+
+impl From<io::Error> for CollectionError {
+    fn from(e: io::Error) -> Self {
+        CollectionError::Io(e)
+    }
+}
+
+impl From<SourceError> for CollectionError {
+    fn from(e: SourceError) -> Self {
+        CollectionError::Source(e)
+    }
+}
+
+// How an image ended up in the tag folder, hard links need both on the same filesystem
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum LinkKind {
+    HardLink,
+    Symlink,
+    Copy,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ImageRef {
+    pub source: String,
+    pub id: String,
+}
+
+// An image that could not be added or removed, the rest of the batch still goes through
+#[derive(Debug, Serialize, Clone)]
+pub struct ImageFailure {
+    pub source: String,
+    pub id: String,
+    pub error: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CollectionItem {
+    pub name: String,
+    pub images: Vec<String>,
+    pub count: usize,
+    // Apparent size, linked files share their space with the downloads folder
+    pub total_size: u64,
+    // Favourite or best rated image, otherwise the first one
+    pub cover: Option<String>,
+    // Provider and id of each image, e.g. for /change-wallpaper
+    pub ids: Vec<ImageRef>,
+    // Wallhaven collection the tag mirrors
+    pub synced_with: Option<sync::SyncLink>,
+}
+
+pub fn sanitize(name: &str) -> Result<String, CollectionError> {
+    let tag = utils::sanitize_tag_name(name);
+    if tag.is_empty() || tag == "." || tag == ".." {
+        return Err(CollectionError::InvalidName(format!("Invalid tag name '{}'", name)));
+    }
+    Ok(tag)
+}
+
+fn existing_tag_dir(name: &str) -> Result<(String, PathBuf), CollectionError> {
+    let tag = sanitize(name)?;
+    let dir = utils::get_collections_directory().join(&tag);
+    if !dir.is_dir() {
+        return Err(CollectionError::NotFound(format!("Tag '{}' does not exist", tag)));
+    }
+    Ok((tag, dir))
+}
+
+pub fn create(name: &str) -> Result<(String, PathBuf), CollectionError> {
+    let tag = sanitize(name)?;
+    let dir = utils::get_collections_directory().join(&tag);
+    utils::ensure_dir(&dir)?;
+    Ok((tag, dir))
+}
+
+pub fn rename(name: &str, new_name: &str) -> Result<(String, PathBuf), CollectionError> {
+    let (tag, dir) = existing_tag_dir(name)?;
+    let new_tag = sanitize(new_name)?;
+    let new_dir = utils::get_collections_directory().join(&new_tag);
+    // On case insensitive filesystems changing only the case finds the tag's own folder
+    if new_dir.exists() && !same_dir(&dir, &new_dir) {
+        return Err(CollectionError::AlreadyExists(format!("Tag '{}' already exists", new_tag)));
+    }
+    fs::rename(&dir, &new_dir)?;
+    sync::rename_tag(&tag, &new_tag);
+    Ok((new_tag, new_dir))
+}
+
+#[cfg(unix)]
+fn same_dir(a: &Path, b: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    match (fs::metadata(a), fs::metadata(b)) {
+        (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+        _ => false,
+    }
+}
+
+// Both resolve to the name stored on disk
+#[cfg(not(unix))]
+fn same_dir(a: &Path, b: &Path) -> bool {
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+// Only removes the links, the downloaded images stay where they are
+pub fn delete(name: &str) -> Result<(), CollectionError> {
+    let (tag, dir) = existing_tag_dir(name)?;
+    fs::remove_dir_all(dir)?;
+    sync::unlink(&tag)?;
+    Ok(())
+}
+
+// Downloads the image if needed and links it into the tag folder, the kind is None if it was already tagged
+pub fn add_image(name: &str, source_name: &str, id: &str) -> Result<(PathBuf, Option<LinkKind>), CollectionError> {
+    let (_, dir) = create(name)?;
+
+    let source = sources::get_or_default(Some(source_name))?;
+    let image: Wallpaper = match library::get(source.name(), id) {
+        Some(entry) if Path::new(&entry.path).is_file() => entry.wallpaper,
+        _ => source.get_by_id(id)?,
+    };
+    link_image(&dir, source.as_ref(), &image)
+}
+
+// Same as add_image for an image whose details are already known, e.g. from a listing
+fn link_image(dir: &Path, source: &dyn ImageSource, image: &Wallpaper) -> Result<(PathBuf, Option<LinkKind>), CollectionError> {
+    let downloads = utils::get_downloads_directory();
+    utils::ensure_dir(&downloads)?;
+    let image_path = download::image::original(source, image, downloads.to_str().unwrap())
+        .map_err(|e| CollectionError::Io(io::Error::other(e.to_string())))?;
+    let image_path = PathBuf::from(image_path);
+
+    let extension = image_path.extension().and_then(|e| e.to_str()).unwrap_or("jpg");
+    let dest = dir.join(format!("{}.{}", image.file_stem(), extension));
+    if dest.exists() {
+        return Ok((dest, None));
+    }
+    let kind = link_or_copy(&image_path, &dest)?;
+    Ok((dest, Some(kind)))
+}
+
+pub fn remove_image(name: &str, source: &str, id: &str) -> Result<(), CollectionError> {
+    let (tag, dir) = existing_tag_dir(name)?;
+    let stem = format!("{}-{}", source, id);
+    let file = fs::read_dir(&dir)?
+        .flatten()
+        .map(|entry| entry.path())
+        .find(|path| path.file_stem().and_then(|s| s.to_str()) == Some(stem.as_str()))
+        .ok_or_else(|| CollectionError::NotFound(format!("{} is not tagged '{}'", library::key(source, id), tag)))?;
+    fs::remove_file(file)?;
+    Ok(())
+}
+
+fn link_or_copy(from: &Path, to: &Path) -> io::Result<LinkKind> {
+    if fs::hard_link(from, to).is_ok() {
+        return Ok(LinkKind::HardLink);
+    }
+    #[cfg(unix)]
+    if std::os::unix::fs::symlink(from, to).is_ok() {
+        return Ok(LinkKind::Symlink);
+    }
+    // Windows only allows symlinks with developer mode or admin rights, so fall back to copying
+    fs::copy(from, to)?;
+    Ok(LinkKind::Copy)
+}
+
+// "<source>-<id>" file names, older copies from the downloads folder are named the same way
+fn image_ref(path: &Path) -> Option<ImageRef> {
+    let stem = path.file_stem()?.to_str()?;
+    sources::SOURCE_NAMES.iter().find_map(|source| {
+        stem.strip_prefix(source)
+            .and_then(|rest| rest.strip_prefix('-'))
+            .filter(|id| !id.is_empty())
+            .map(|id| ImageRef { source: source.to_string(), id: id.to_string() })
+    })
+}
+
+pub fn list() -> io::Result<Vec<CollectionItem>> {
+    let collections_dir = utils::get_collections_directory();
+    utils::ensure_dir(&collections_dir)?;
+    let library = library::load();
+    let mut links = sync::links();
+
+    let mut items = Vec::new();
+    for entry in fs::read_dir(&collections_dir)?.flatten() {
+        let dir = entry.path();
+        if !dir.is_dir() {
+            continue;
+        }
+        let name = dir.file_name().unwrap_or_default().to_string_lossy().to_string();
+        let mut images: Vec<PathBuf> = fs::read_dir(&dir)?
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.is_file())
+            .collect();
+        images.sort();
+
+        let ids: Vec<ImageRef> = images.iter().filter_map(|path| image_ref(path)).collect();
+        let total_size = images.iter().filter_map(|path| fs::metadata(path).ok()).map(|m| m.len()).sum();
+        let cover = images.iter()
+            .max_by(|a, b| {
+                let weight = |path: &PathBuf| image_ref(path)
+                    .and_then(|image| library.entries.get(&library::key(&image.source, &image.id)))
+                    .map(|entry| entry.weight())
+                    .unwrap_or(0.0);
+                // Ties go to the first image
+                weight(a).total_cmp(&weight(b)).then(b.cmp(a))
+            })
+            .map(|path| path.to_string_lossy().to_string());
+
+        items.push(CollectionItem {
+            synced_with: links.remove(&name),
+            name,
+            count: images.len(),
+            images: images.iter().map(|path| path.to_string_lossy().to_string()).collect(),
+            total_size,
+            cover,
+            ids,
+        });
+    }
+    items.sort_by_key(|item| item.name.to_lowercase());
+    Ok(items)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_dir_tells_folders_apart() {
+        let root = std::env::temp_dir().join(format!("wallpaper_changer-same-dir-{}", std::process::id()));
+        fs::create_dir_all(root.join("cats")).unwrap();
+        fs::create_dir_all(root.join("dogs")).unwrap();
+
+        assert!(same_dir(&root.join("cats"), &root.join("cats")));
+        assert!(same_dir(&root.join("cats"), &root.join("dogs").join("..").join("cats")));
+        assert!(!same_dir(&root.join("cats"), &root.join("dogs")));
+        assert!(!same_dir(&root.join("cats"), &root.join("birds")));
+        // Only one folder when the filesystem ignores case, two different tags otherwise
+        if fs::create_dir(root.join("Cats")).is_ok() {
+            assert!(!same_dir(&root.join("cats"), &root.join("Cats")));
+        } else {
+            assert!(same_dir(&root.join("cats"), &root.join("Cats")));
+        }
+
+        fs::remove_dir_all(root).unwrap();
+    }
+}
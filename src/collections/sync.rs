@@ -0,0 +1,279 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex};
+use std::thread;
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use crate::models::wallpaper::Wallpaper;
+use crate::sources::wallhaven::WallhavenSource;
+use crate::{scheduler, utils};
+use super::{CollectionError, ImageFailure, ImageRef};
+
+// A tag can mirror a Wallhaven user collection. The link and the ids the last sync brought in are kept
+// in wallhaven_sync.json, so a re-sync only downloads new images and only removes images it added itself
+
+const SYNC_FILE_NAME: &str = "wallhaven_sync.json";
+// Wallhaven allows 45 API calls a minute
+const PAGE_DELAY: Duration = Duration::from_millis(1500);
+
+static LOCK: Mutex<()> = Mutex::new(());
+// Progress of the background syncs started since the server came up, by tag
+static JOBS: LazyLock<Mutex<HashMap<String, SyncStatus>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SyncLink {
+    pub username: String,
+    pub collection_id: u64,
+    #[serde(default)]
+    pub label: Option<String>,
+    #[serde(default)]
+    pub last_synced: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct SyncedTag {
+    #[serde(flatten)]
+    link: SyncLink,
+    #[serde(default)]
+    ids: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default)]
+struct SyncState {
+    #[serde(default)]
+    tags: BTreeMap<String, SyncedTag>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct SyncReport {
+    pub tag: String,
+    pub collection: SyncLink,
+    pub added: Vec<ImageRef>,
+    pub removed: Vec<ImageRef>,
+    // Images already in the tag
+    pub unchanged: usize,
+    pub failed: Vec<ImageFailure>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum SyncStatus {
+    // Waiting for the tags before it in a sync of every tag
+    Queued,
+    Running { started_at: u64 },
+    Finished { finished_at: u64, report: SyncReport },
+    Failed { finished_at: u64, error: String },
+}
+
+fn get_sync_path() -> PathBuf {
+    utils::get_user_data_directory().join(SYNC_FILE_NAME)
+}
+
+fn read() -> SyncState {
+    utils::read_json_file(&get_sync_path())
+}
+
+fn update<T, F: FnOnce(&mut SyncState) -> T>(change: F) -> Result<T, Box<dyn Error + Send + Sync>> {
+    let _guard = LOCK.lock().unwrap();
+    let mut state = read();
+    let result = change(&mut state);
+    utils::write_json_file(&get_sync_path(), &state)?;
+    Ok(result)
+}
+
+pub fn links() -> BTreeMap<String, SyncLink> {
+    let _guard = LOCK.lock().unwrap();
+    read().tags.into_iter().map(|(tag, synced)| (tag, synced.link)).collect()
+}
+
+// Called when a tag is renamed or deleted, the images themselves are handled by the caller
+pub fn rename_tag(tag: &str, new_tag: &str) {
+    let result = update(|state| {
+        if let Some(synced) = state.tags.remove(tag) {
+            state.tags.insert(new_tag.to_string(), synced);
+        }
+    });
+    if let Err(e) = result {
+        eprintln!("Failed to move the Wallhaven sync of '{}' to '{}': {}", tag, new_tag, e);
+    }
+}
+
+// Stops mirroring, the images stay in the tag
+pub fn unlink(name: &str) -> Result<Option<SyncLink>, CollectionError> {
+    let tag = super::sanitize(name)?;
+    update(|state| state.tags.remove(&tag).map(|synced| synced.link))
+        .map_err(|e| CollectionError::Io(std::io::Error::other(e.to_string())))
+}
+
+// Every image of the collection, page by page
+fn fetch_all(username: &str, collection_id: u64) -> Result<Vec<Wallpaper>, CollectionError> {
+    let mut images: Vec<Wallpaper> = Vec::new();
+    let mut seen = HashSet::new();
+    let mut page = 1;
+    loop {
+        let response = WallhavenSource::collection_page(username, collection_id, page)?;
+        for image in response.data {
+            if seen.insert(image.id.clone()) {
+                images.push(image.into());
+            }
+        }
+        if response.meta.current_page.max(0) as u32 >= response.meta.last_page.max(0) as u32 {
+            return Ok(images);
+        }
+        page += 1;
+        thread::sleep(PAGE_DELAY);
+    }
+}
+
+fn tagged_wallhaven_ids(dir: &Path) -> Result<HashSet<String>, CollectionError> {
+    Ok(fs::read_dir(dir)?
+        .flatten()
+        .filter_map(|entry| super::image_ref(&entry.path()))
+        .filter(|image| image.source == "wallhaven")
+        .map(|image| image.id)
+        .collect())
+}
+
+// Links the tag to the given collection (and syncs it), or re-syncs the collection it is already linked to
+pub fn sync(name: &str, collection: Option<(&str, u64)>) -> Result<SyncReport, CollectionError> {
+    let tag = super::sanitize(name)?;
+    let previous = {
+        let _guard = LOCK.lock().unwrap();
+        read().tags.remove(&tag)
+    };
+    let was_linked = previous.is_some();
+
+    let mut link = match (collection, &previous) {
+        (Some((username, collection_id)), previous) => {
+            let label = previous.as_ref()
+                .filter(|synced| synced.link.username == username && synced.link.collection_id == collection_id)
+                .and_then(|synced| synced.link.label.clone());
+            SyncLink { username: username.trim().to_string(), collection_id, label, last_synced: None }
+        },
+        (None, Some(synced)) => synced.link.clone(),
+        (None, None) => return Err(CollectionError::NotFound(format!("Tag '{}' is not synced with a Wallhaven collection", tag))),
+    };
+    // Only public collections are listed by username, private ones keep no label
+    if link.label.is_none()
+        && let Ok(collections) = WallhavenSource::collections(Some(&link.username)) {
+        link.label = collections.into_iter().find(|c| c.id == link.collection_id).map(|c| c.label);
+    }
+
+    let remote = fetch_all(&link.username, link.collection_id)?;
+    let (_, dir) = super::create(&tag)?;
+    let tagged = tagged_wallhaven_ids(&dir)?;
+    let previous_ids: HashSet<String> = previous.map(|synced| synced.ids.into_iter().collect()).unwrap_or_default();
+    let remote_ids: HashSet<&str> = remote.iter().map(|image| image.id.as_str()).collect();
+
+    let mut report = SyncReport { tag: tag.clone(), collection: link.clone(), added: Vec::new(), removed: Vec::new(), unchanged: 0, failed: Vec::new() };
+    // Images tagged by hand before the first sync are not ours to remove later
+    let mut synced_ids: Vec<String> = remote.iter()
+        .filter(|image| previous_ids.contains(&image.id) && tagged.contains(&image.id))
+        .map(|image| image.id.clone())
+        .collect();
+
+    for image in &remote {
+        if tagged.contains(&image.id) {
+            report.unchanged += 1;
+            continue;
+        }
+        match super::link_image(&dir, &WallhavenSource, image) {
+            Ok(_) => {
+                synced_ids.push(image.id.clone());
+                report.added.push(ImageRef { source: image.source.clone(), id: image.id.clone() });
+            },
+            Err(e) => report.failed.push(ImageFailure { source: image.source.clone(), id: image.id.clone(), error: e.to_string() }),
+        }
+    }
+
+    for id in previous_ids.iter().filter(|id| !remote_ids.contains(id.as_str()) && tagged.contains(*id)) {
+        let image = ImageRef { source: "wallhaven".to_string(), id: id.clone() };
+        match super::remove_image(&tag, &image.source, &image.id) {
+            Ok(()) => report.removed.push(image),
+            Err(e) => report.failed.push(ImageFailure { source: image.source, id: image.id, error: e.to_string() }),
+        }
+    }
+
+    link.last_synced = Some(scheduler::now());
+    report.collection = link.clone();
+    let saved = update(|state| {
+        // Unlinked, renamed or deleted while the sync ran, saving now would bring the link back
+        if !dir.is_dir() || (was_linked && !state.tags.contains_key(&tag)) {
+            return false;
+        }
+        state.tags.insert(tag.clone(), SyncedTag { link, ids: synced_ids });
+        true
+    }).map_err(|e| CollectionError::Io(std::io::Error::other(e.to_string())))?;
+    if !saved {
+        eprintln!("Tag '{}' was unlinked from Wallhaven during the sync, not saving it", tag);
+    }
+    Ok(report)
+}
+
+fn run_job(tag: &str, collection: Option<(&str, u64)>) {
+    JOBS.lock().unwrap().insert(tag.to_string(), SyncStatus::Running { started_at: scheduler::now() });
+    let status = match sync(tag, collection) {
+        Ok(report) => SyncStatus::Finished { finished_at: scheduler::now(), report },
+        Err(e) => {
+            eprintln!("Failed to sync '{}' with Wallhaven: {}", tag, e);
+            SyncStatus::Failed { finished_at: scheduler::now(), error: e.to_string() }
+        }
+    };
+    JOBS.lock().unwrap().insert(tag.to_string(), status);
+}
+
+// Runs sync() on a background thread, a sync pulls every page of the collection and can take minutes
+// Returns the sanitized tag name, the outcome is read back with status()
+pub fn start(name: &str, collection: Option<(String, u64)>) -> Result<String, CollectionError> {
+    let tag = super::sanitize(name)?;
+    {
+        let mut jobs = JOBS.lock().unwrap();
+        // The link of a first sync is only saved once it is done, so a running job is checked first
+        if matches!(jobs.get(&tag), Some(SyncStatus::Queued | SyncStatus::Running { .. })) {
+            return Err(CollectionError::AlreadyExists(format!("Tag '{}' is already being synced", tag)));
+        }
+        if collection.is_none() && !links().contains_key(&tag) {
+            return Err(CollectionError::NotFound(format!("Tag '{}' is not synced with a Wallhaven collection", tag)));
+        }
+        jobs.insert(tag.clone(), SyncStatus::Queued);
+    }
+    let job_tag = tag.clone();
+    thread::spawn(move || {
+        let collection = collection.as_ref().map(|(username, id)| (username.as_str(), *id));
+        run_job(&job_tag, collection);
+    });
+    Ok(tag)
+}
+
+// Re-syncs every linked tag one after the other on a background thread, so the rate limit is shared
+// Returns the tags that were queued, tags already being synced are left to the running job
+pub fn start_all() -> Vec<String> {
+    let tags: Vec<String> = {
+        let mut jobs = JOBS.lock().unwrap();
+        let tags: Vec<String> = links().into_keys()
+            .filter(|tag| !matches!(jobs.get(tag), Some(SyncStatus::Queued | SyncStatus::Running { .. })))
+            .collect();
+        for tag in &tags {
+            jobs.insert(tag.clone(), SyncStatus::Queued);
+        }
+        tags
+    };
+    let queued = tags.clone();
+    thread::spawn(move || {
+        for tag in tags {
+            run_job(&tag, None);
+        }
+    });
+    queued
+}
+
+pub fn status(name: &str) -> Result<Option<SyncStatus>, CollectionError> {
+    let tag = super::sanitize(name)?;
+    Ok(JOBS.lock().unwrap().get(&tag).cloned())
+}
+
+pub fn statuses() -> BTreeMap<String, SyncStatus> {
+    JOBS.lock().unwrap().iter().map(|(tag, status)| (tag.clone(), status.clone())).collect()
+}
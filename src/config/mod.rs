@@ -0,0 +1,78 @@
+use std::error::Error;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use serde::{Deserialize, Serialize};
+use crate::utils;
+use crate::daily::DailyProvider;
+use crate::wallpaper::FitMode;
+
+const CONFIG_FILE_NAME: &str = "config.json";
+
+static LOCK: Mutex<()> = Mutex::new(());
+
+// User settings persisted between runs in the user data directory
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct AppConfig {
+    #[serde(default)]
+    pub wallhaven_api_key: Option<String>,
+    // Linux only: force a wallpaper backend instead of detecting it, see wallpaper::linux::BACKEND_NAMES
+    #[serde(default)]
+    pub wallpaper_backend: Option<String>,
+    #[serde(default)]
+    pub fit_mode: Option<FitMode>,
+    #[serde(default)]
+    pub set_lock_screen: bool,
+    #[serde(default)]
+    pub span_monitors: bool,
+    // Folders browsed by the "local" provider, searched recursively
+    #[serde(default)]
+    pub local_folders: Vec<String>,
+    #[serde(default)]
+    pub unsplash_api_key: Option<String>,
+    #[serde(default)]
+    pub pexels_api_key: Option<String>,
+    // Override the provider API addresses, e.g. to point at a mock server
+    #[serde(default)]
+    pub unsplash_api_url: Option<String>,
+    #[serde(default)]
+    pub pexels_api_url: Option<String>,
+    #[serde(default)]
+    pub reddit_api_url: Option<String>,
+    // Tried in order by /daily and --daily, defaults to Bing
+    #[serde(default)]
+    pub daily_providers: Vec<DailyProvider>,
+    // Falls back to NASA's shared DEMO_KEY
+    #[serde(default)]
+    pub nasa_api_key: Option<String>,
+    // Number of wallpapers kept in the history, defaults to 500
+    #[serde(default)]
+    pub history_limit: Option<usize>,
+    // Set the last wallpaper again when the server starts
+    #[serde(default)]
+    pub restore_on_startup: bool,
+    // How often to check for wallpaper changes made outside the app, defaults to 30, 0 turns it off
+    #[serde(default)]
+    pub watch_interval_seconds: Option<u64>,
+    // Thumbnails downloaded at the same time for a page of search results, defaults to 6
+    #[serde(default)]
+    pub thumbnail_concurrency: Option<usize>,
+}
+
+pub fn get_config_path() -> PathBuf {
+    utils::get_user_data_directory().join(CONFIG_FILE_NAME)
+}
+
+// Missing or unreadable config falls back to the defaults, so a broken file never stops the app
+pub fn load() -> AppConfig {
+    let _guard = LOCK.lock().unwrap();
+    utils::read_json_file(&get_config_path())
+}
+
+// Loads, modifies and saves the config in one go
+pub fn update<F: FnOnce(&mut AppConfig)>(change: F) -> Result<AppConfig, Box<dyn Error + Send + Sync>> {
+    let _guard = LOCK.lock().unwrap();
+    let mut config: AppConfig = utils::read_json_file(&get_config_path());
+    change(&mut config);
+    utils::write_json_file(&get_config_path(), &config)?;
+    Ok(config)
+}
@@ -0,0 +1,227 @@
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use crate::models::daily::{ApodResponse, BingArchiveResponse};
+use crate::{config, download, utils, wallpaper};
+
+// Daily wallpapers from Bing's Image of the Day and NASA's Astronomy Picture of the Day
+// The image is saved in the downloads folder with its title/copyright/explanation in a .json file next to it
+
+const BING_ARCHIVE_URL: &str = "https://www.bing.com/HPImageArchive.aspx?format=js&idx=0&n=1&mkt=en-US";
+const BING_URL: &str = "https://www.bing.com";
+const APOD_API: &str = "https://api.nasa.gov/planetary";
+// Shared rate limited key, fine for one request a day
+const APOD_DEMO_KEY: &str = "DEMO_KEY";
+// How often the daily loop checks for a new image
+const CHECK_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum DailyProvider {
+    Bing,
+    Apod,
+}
+
+impl DailyProvider {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DailyProvider::Bing => "bing",
+            DailyProvider::Apod => "apod",
+        }
+    }
+}
+
+impl fmt::Display for DailyProvider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for DailyProvider {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_lowercase().as_str() {
+            "bing" => Ok(DailyProvider::Bing),
+            "apod" | "nasa" => Ok(DailyProvider::Apod),
+            other => Err(format!("Invalid daily provider '{}', expected bing or apod", other)),
+        }
+    }
+}
+
+// Comma separated list tried in order, e.g. "apod,bing" falls back to Bing on days APOD is a video
+// None uses "daily_providers" from the config, or Bing if that is empty
+pub fn parse_providers(value: Option<&str>) -> Result<Vec<DailyProvider>, String> {
+    let providers = match value.map(str::trim).filter(|v| !v.is_empty()) {
+        Some(value) => value.split(',')
+            .map(DailyProvider::from_str)
+            .collect::<Result<Vec<_>, _>>()?,
+        None => config::load().daily_providers,
+    };
+    if providers.is_empty() {
+        return Ok(vec![DailyProvider::Bing]);
+    }
+    Ok(providers)
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DailyImage {
+    pub provider: DailyProvider,
+    // Date as given by the provider, used to tell whether the image has changed
+    pub date: String,
+    pub title: Option<String>,
+    pub copyright: Option<String>,
+    pub explanation: Option<String>,
+    pub image_url: String,
+    // Page with more details about the image
+    pub page_url: Option<String>,
+    // Where the image was saved, set once downloaded
+    pub path: Option<String>,
+}
+
+impl DailyImage {
+    fn file_stem(&self) -> String {
+        let date: String = self.date.chars().filter(|c| c.is_ascii_alphanumeric() || *c == '-').collect();
+        format!("{}-{}", self.provider, date)
+    }
+
+    fn extension(&self) -> &'static str {
+        let url = self.image_url.split(['?', '#']).next().unwrap_or(&self.image_url).to_lowercase();
+        match url.rsplit('.').next() {
+            Some("png") => "png",
+            Some("gif") => "gif",
+            Some("webp") => "webp",
+            _ => "jpg"
+        }
+    }
+}
+
+// Today's image from the provider, None when there's nothing to use (APOD posts videos some days)
+pub fn latest(provider: DailyProvider) -> Result<Option<DailyImage>, Box<dyn Error + Send + Sync>> {
+    match provider {
+        DailyProvider::Bing => {
+            let response = utils::api_request(BING_ARCHIVE_URL)
+                .call()?
+                .body_mut()
+                .read_json::<BingArchiveResponse>()?;
+            let image = response.images.into_iter().next().ok_or("Bing returned no image")?;
+            Ok(Some(DailyImage {
+                provider,
+                date: image.startdate,
+                title: image.title,
+                copyright: image.copyright,
+                explanation: None,
+                // Full UHD version rather than the 1920x1080 one in "url"
+                image_url: format!("{}{}_UHD.jpg", BING_URL, image.urlbase),
+                page_url: image.copyrightlink,
+                path: None,
+            }))
+        },
+        DailyProvider::Apod => {
+            let api_key = config::load().nasa_api_key
+                .filter(|key| !key.trim().is_empty())
+                .unwrap_or_else(|| APOD_DEMO_KEY.to_string());
+            let url = utils::api_url(APOD_API, "apod", &[("api_key", api_key.trim().to_string())]);
+            let response = utils::api_request(&url)
+                .call()?
+                .body_mut()
+                .read_json::<ApodResponse>()?;
+            if response.media_type != "image" {
+                println!("Daily: APOD for {} is a {}, skipping", response.date, response.media_type);
+                return Ok(None);
+            }
+            // Archive pages are named after the date, e.g. 2024-01-31 is ap240131.html
+            let short_date = response.date.replace('-', "");
+            let page_url = short_date.get(2..).map(|date| format!("https://apod.nasa.gov/apod/ap{}.html", date));
+            Ok(Some(DailyImage {
+                provider,
+                page_url,
+                date: response.date,
+                title: Some(response.title),
+                copyright: response.copyright.map(|c| c.trim().to_string()),
+                explanation: response.explanation,
+                image_url: response.hdurl.unwrap_or(response.url),
+                path: None,
+            }))
+        }
+    }
+}
+
+// First provider in the list with an image today
+pub fn latest_of(providers: &[DailyProvider]) -> Result<Option<DailyImage>, Box<dyn Error + Send + Sync>> {
+    let mut last_error = None;
+    for provider in providers {
+        match latest(*provider) {
+            Ok(Some(image)) => return Ok(Some(image)),
+            Ok(None) => {},
+            Err(e) => {
+                eprintln!("Daily: failed to fetch {}: {}", provider, e);
+                last_error = Some(e);
+            }
+        }
+    }
+    match last_error {
+        Some(e) => Err(e),
+        None => Ok(None)
+    }
+}
+
+// Saves the image and its metadata into the downloads folder, reusing them if already there
+pub fn download(image: &mut DailyImage) -> Result<String, Box<dyn Error + Send + Sync>> {
+    let downloads = utils::get_downloads_directory();
+    utils::ensure_dir(&downloads)?;
+
+    let file_path = downloads.join(format!("{}.{}", image.file_stem(), image.extension()));
+    if !file_path.exists() {
+        println!("Daily: downloading {}", image.image_url);
+        download::image::to_file(&image.image_url, &file_path)?;
+    }
+
+    let path = file_path.to_string_lossy().to_string();
+    image.path = Some(path.clone());
+    fs::write(metadata_path(&file_path), serde_json::to_string_pretty(image)?)?;
+    Ok(path)
+}
+
+fn metadata_path(image_path: &Path) -> PathBuf {
+    image_path.with_extension("json")
+}
+
+// Fetches today's image and sets it as the wallpaper
+pub fn apply(providers: &[DailyProvider]) -> Result<Option<DailyImage>, Box<dyn Error + Send + Sync>> {
+    let Some(mut image) = latest_of(providers)? else {
+        return Ok(None);
+    };
+    let path = download(&mut image)?;
+    wallpaper::change(&path)?;
+    println!("Daily: changed wallpaper to {}", path);
+    Ok(Some(image))
+}
+
+// Blocking loop for the --daily flag, changes the wallpaper whenever a new daily image comes out
+pub fn run_forever(providers: &[DailyProvider]) -> ! {
+    let mut last_applied: Option<(DailyProvider, String)> = None;
+    loop {
+        match latest_of(providers) {
+            Ok(Some(mut image)) => {
+                let key = (image.provider, image.date.clone());
+                if last_applied.as_ref() != Some(&key) {
+                    match download(&mut image).and_then(|path| wallpaper::change(&path).map(|_| path).map_err(|e| e.into())) {
+                        Ok(path) => {
+                            println!("Daily: changed wallpaper to {}", path);
+                            last_applied = Some(key);
+                        },
+                        Err(e) => eprintln!("Daily: failed to change wallpaper: {}", e)
+                    }
+                }
+            },
+            Ok(None) => println!("Daily: nothing to set today"),
+            Err(e) => eprintln!("Daily: {}", e)
+        }
+        std::thread::sleep(CHECK_INTERVAL);
+    }
+}
@@ -1,14 +1,38 @@
+use std::collections::HashMap;
 use std::error::Error;
-use std::fs;
-use std::fs::File;
-use std::io::{copy, Cursor, Read};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Read, Write};
 use std::path::{Path, PathBuf};
-use crate::models::wallhaven::WHImageData;
-use crate::utils;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, LazyLock, Mutex};
+use std::thread;
+use crate::models::wallpaper::Wallpaper;
+use crate::sources::ImageSource;
+use crate::{library, utils};
 
-pub fn thumbnail(image: &&WHImageData, local_path: &str) -> Result<String, Box<dyn Error + Send + Sync>> {
-    let file_name = format!("wallhaven-{}.{}",
-                            &image.id,
+// Attempts per download, each one resumes where the previous one stopped
+const ATTEMPTS: usize = 3;
+pub const DEFAULT_THUMBNAIL_CONCURRENCY: usize = 6;
+
+// Downloads in progress by destination, so two requests for the same image don't write into the same .part file
+static IN_PROGRESS: LazyLock<Mutex<HashMap<PathBuf, Arc<Mutex<()>>>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+// Images of the local provider are already on disk (file:// URLs) and are used where they are instead of being copied
+// Other providers never point at local files, whatever their metadata says (e.g. from an imported archive)
+fn local_file(source: &dyn ImageSource, url: &str) -> Option<String> {
+    if source.name() != "local" {
+        return None;
+    }
+    url.strip_prefix("file://").map(str::to_string)
+}
+
+pub fn thumbnail(source: &dyn ImageSource, image: &Wallpaper, local_path: &str) -> Result<String, Box<dyn Error + Send + Sync>> {
+    if let Some(path) = local_file(source, &source.thumbnail_url(image)) {
+        return Ok(path);
+    }
+
+    let file_name = format!("{}.{}",
+                            image.file_stem(),
                             utils::get_file_extension(&image.file_type)
     );
 
@@ -22,30 +46,62 @@ pub fn thumbnail(image: &&WHImageData, local_path: &str) -> Result<String, Box<d
         println!("File {} already exists, skipping download", &file_path);
         return Ok(file_path);
     }
+    let thumbnail_url = source.thumbnail_url(image);
     println!("Image ID: {}", image.id);
     println!("Image URL: {}", image.url);
-    println!("Image path: {}", image.path);
-    println!("Image thumbs: {}", image.thumbs.small);
-
-    // Create the output file
-    let mut image_file = File::create(&file_path).expect("Failed to create file");
-
-    let image_request = ureq::get(&image.thumbs.small)
-        .call()?;
+    println!("Image thumbs: {}", thumbnail_url);
 
-    let (_, body) = image_request.into_parts();
+    download(&thumbnail_url, Path::new(&file_path), None)?;
+    Ok(file_path)
+}
 
-    let mut bytes_buf: Vec<u8> = Vec::with_capacity(1000);
+// Downloads the thumbnails of a page of results on up to `concurrency` threads,
+// the results come back in the same order as the images
+pub fn thumbnails(source: &dyn ImageSource, images: &[Wallpaper], local_path: &str, concurrency: usize) -> Vec<Result<String, String>> {
+    let next = AtomicUsize::new(0);
+    let workers = concurrency.clamp(1, images.len().max(1));
+    let mut results: Vec<Result<String, String>> = images.iter()
+        .map(|_| Err("Thumbnail download did not finish".to_string()))
+        .collect();
 
-    body.into_reader()
-        .read_to_end(&mut bytes_buf)?;
+    let done: Vec<(usize, Result<String, String>)> = thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers)
+            .map(|_| scope.spawn(|| {
+                let mut done = Vec::new();
+                // Each worker takes the next image until none are left
+                loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(image) = images.get(index) else {
+                        return done;
+                    };
+                    done.push((index, thumbnail(source, image, local_path).map_err(|e| e.to_string())));
+                }
+            }))
+            .collect();
+        handles.into_iter()
+            .flat_map(|handle| handle.join().unwrap_or_default())
+            .collect()
+    });
+    for (index, result) in done {
+        results[index] = result;
+    }
+    results
+}
 
-    // // Copy the response body to the file
-    copy(&mut Cursor::new(bytes_buf), &mut image_file)?;
-    Ok(file_path)
+// Downloads the full size image and records its metadata in the library
+pub fn original(source: &dyn ImageSource, image: &Wallpaper, local_path: &str) -> Result<String, Box<dyn Error + Send + Sync>> {
+    let path = fetch_original(source, image, local_path)?;
+    if let Err(e) = library::record(image, &path) {
+        eprintln!("Failed to record {} in the library: {}", image.file_stem(), e);
+    }
+    Ok(path)
 }
 
-pub fn original(image: &WHImageData, local_path: &str) -> Result<String, Box<dyn Error + Send + Sync>> {
+fn fetch_original(source: &dyn ImageSource, image: &Wallpaper, local_path: &str) -> Result<String, Box<dyn Error + Send + Sync>> {
+    if let Some(path) = local_file(source, &source.original_url(image)) {
+        return Ok(path);
+    }
+
     // First, check if the directory exists and create it if it doesn't
     if !Path::new(local_path).exists() {
         fs::create_dir_all(local_path)?;
@@ -54,8 +110,8 @@ pub fn original(image: &WHImageData, local_path: &str) -> Result<String, Box<dyn
 
     // let file_path = format!("{}/wallhaven-{}.{}", local_path, &image.id, utils::get_file_extension(&image.file_type));
 
-    let file_name = format!("wallhaven-{}.{}",
-                            &image.id,
+    let file_name = format!("{}.{}",
+                            image.file_stem(),
                             utils::get_file_extension(&image.file_type)
     );
 
@@ -68,30 +124,328 @@ pub fn original(image: &WHImageData, local_path: &str) -> Result<String, Box<dyn
 
     println!("Original: Image path: {}", file_path);
 
-    // Check if file already exists
-    if Path::new(&file_path).exists() {
-        println!("File {} already exists, skipping download", file_path);
-        return Ok(file_path);
+    // Only complete downloads get the final name, but older versions could leave a truncated file behind
+    if let Ok(metadata) = fs::metadata(&file_path) {
+        if image.file_size.is_none_or(|size| size == metadata.len()) {
+            println!("File {} already exists, skipping download", file_path);
+            return Ok(file_path);
+        }
+        println!("File {} is {} bytes instead of {:?}, downloading again", file_path, metadata.len(), image.file_size);
     }
+    let original_url = source.original_url(image);
     println!("Image ID: {}", image.id);
     println!("Image URL: {}", image.url);
-    println!("Image path: {}", image.path);
-    println!("Image thumbs: {}", image.thumbs.small);
+    println!("Image path: {}", original_url);
 
-    // Create the output file
-    let mut image_file = File::create(&file_path)?;  // Changed to use ? operator for better error handling
+    download(&original_url, Path::new(&file_path), image.file_size)?;
+    Ok(file_path)
+}
+// Downloads a URL straight to the given path, used for images that don't come from an ImageSource
+pub fn to_file(url: &str, file_path: &Path) -> Result<(), Box<dyn Error + Send + Sync>> {
+    if let Some(parent) = file_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    download(url, file_path, None)
+}
 
-    let image_request = ureq::get(&image.path)
-        .call()?;
+// Streams the URL into <path>.part, which only gets the real name once the whole file arrived
+// The size is checked against expected_size (e.g. Wallhaven's file_size) or else the Content-Length
+fn download(url: &str, path: &Path, expected_size: Option<u64>) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let lock = IN_PROGRESS.lock().unwrap().entry(path.to_path_buf()).or_default().clone();
+    let result = {
+        let _guard = lock.lock().unwrap();
+        // Whoever held the lock before may have just finished the same download
+        if fs::metadata(path).is_ok_and(|m| expected_size.is_none_or(|size| size == m.len())) {
+            Ok(())
+        } else {
+            download_locked(url, path, expected_size)
+        }
+    };
+    let mut in_progress = IN_PROGRESS.lock().unwrap();
+    // Only the map and this call still hold it, nobody else is waiting
+    if Arc::strong_count(&lock) == 2 {
+        in_progress.remove(path);
+    }
+    result
+}
 
-    let (_, body) = image_request.into_parts();
+fn download_locked(url: &str, path: &Path, expected_size: Option<u64>) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let partial = partial_path(path);
+    let mut attempt = 1;
+    loop {
+        match download_partial(url, &partial, expected_size) {
+            Ok(()) => break,
+            Err(AttemptError::Interrupted(e)) if attempt < ATTEMPTS => {
+                eprintln!("Download of {} failed ({}), resuming", url, e);
+                attempt += 1;
+            },
+            Err(AttemptError::Interrupted(e) | AttemptError::Failed(e)) => return Err(e),
+        }
+    }
+    fs::rename(&partial, path)?;
+    Ok(())
+}
 
-    let mut bytes_buf: Vec<u8> = Vec::with_capacity(1000);
+// Dropped connections, timeouts and bodies that end early are worth another try, the next attempt resumes them
+// HTTP errors (e.g. 404), size mismatches and errors writing the local file (e.g. a full disk) are not
+enum AttemptError {
+    Interrupted(Box<dyn Error + Send + Sync>),
+    Failed(Box<dyn Error + Send + Sync>),
+}
 
-    body.into_reader()
-        .read_to_end(&mut bytes_buf)?;
+// Anything touching the local file
+impl From<io::Error> for AttemptError {
+    fn from(e: io::Error) -> Self {
+        AttemptError::Failed(e.into())
+    }
+}
 
-    // Copy the response body to the file
-    copy(&mut Cursor::new(bytes_buf), &mut image_file)?;
-    Ok(file_path)
-}
\ No newline at end of file
+impl From<String> for AttemptError {
+    fn from(message: String) -> Self {
+        AttemptError::Failed(message.into())
+    }
+}
+
+impl From<ureq::Error> for AttemptError {
+    fn from(e: ureq::Error) -> Self {
+        match e {
+            ureq::Error::Io(_) | ureq::Error::Timeout(_) | ureq::Error::ConnectionFailed
+            | ureq::Error::HostNotFound | ureq::Error::Protocol(_) => AttemptError::Interrupted(e.into()),
+            e => AttemptError::Failed(e.into()),
+        }
+    }
+}
+
+fn partial_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".part");
+    path.with_file_name(name)
+}
+
+// Continues a partial file left by an interrupted download with a Range request, servers that
+// ignore the range send the whole file again and it is written from the start
+fn download_partial(url: &str, partial: &Path, expected_size: Option<u64>) -> Result<(), AttemptError> {
+    let mut offset = fs::metadata(partial).map(|m| m.len()).unwrap_or(0);
+    if expected_size.is_some_and(|size| offset > size) {
+        offset = 0;
+    }
+    if offset > 0 && expected_size == Some(offset) {
+        return Ok(());
+    }
+
+    let mut request = utils::api_request(url);
+    if offset > 0 {
+        println!("Resuming {} from byte {}", url, offset);
+        request = request.header("Range", format!("bytes={}-", offset));
+    }
+    let response = match request.call() {
+        Ok(response) => response,
+        // The partial file is no use if the server can't continue it
+        Err(ureq::Error::StatusCode(416)) if offset > 0 => {
+            fs::remove_file(partial)?;
+            // The server's copy is shorter than the partial file, fetching it again would only come up short
+            if let Some(expected_size) = expected_size {
+                return Err(format!("{} is smaller than the expected {} bytes", url, expected_size).into());
+            }
+            return download_partial(url, partial, expected_size);
+        },
+        Err(e) => return Err(e.into()),
+    };
+
+    let resumed = offset > 0 && response.status().as_u16() == 206;
+    if resumed {
+        let start = response.headers().get("content-range")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("bytes "))
+            .and_then(|value| value.split('-').next())
+            .and_then(|start| start.trim().parse::<u64>().ok());
+        if start != Some(offset) {
+            fs::remove_file(partial)?;
+            return Err(format!("{} sent a different range than asked for", url).into());
+        }
+    } else {
+        offset = 0;
+    }
+
+    let (_, body) = response.into_parts();
+    let expected_size = expected_size.or(body.content_length().map(|length| length + offset));
+    let file = if resumed {
+        OpenOptions::new().append(true).open(partial)?
+    } else {
+        File::create(partial)?
+    };
+    let mut writer = BufWriter::new(file);
+    // Copied by hand so a failed read (the connection) can be told apart from a failed write (the disk)
+    let mut reader = body.into_reader();
+    let mut buffer = vec![0u8; 64 * 1024];
+    loop {
+        let read = reader.read(&mut buffer).map_err(|e| AttemptError::Interrupted(e.into()))?;
+        if read == 0 {
+            break;
+        }
+        writer.write_all(&buffer[..read])?;
+    }
+    let file = writer.into_inner().map_err(|e| e.into_error())?;
+    file.sync_all()?;
+
+    let size = file.metadata()?.len();
+    match expected_size {
+        // A body that ended early is an interrupted download, the next attempt resumes it with a Range request
+        Some(expected_size) if size < expected_size => Err(AttemptError::Interrupted(
+            format!("Downloaded {} bytes of {} instead of {}", size, url, expected_size).into()
+        )),
+        // Too long never becomes right
+        Some(expected_size) if size > expected_size => {
+            fs::remove_file(partial)?;
+            Err(format!("Downloaded {} bytes of {} instead of {}", size, url, expected_size).into())
+        },
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, process};
+    use crate::sources::mock_server::MockServer;
+
+    fn response(status: &str, headers: &str, body: &[u8]) -> Vec<u8> {
+        let mut response = format!("HTTP/1.1 {}\r\n{}Connection: close\r\n\r\n", status, headers).into_bytes();
+        response.extend_from_slice(body);
+        response
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("wallpaper_changer-download-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn downloads_to_the_final_name() {
+        let dir = temp_dir("full");
+        let server = MockServer::start_raw(vec![response("200 OK", "Content-Length: 10\r\n", b"0123456789")]);
+        let path = dir.join("image.jpg");
+
+        download(&format!("{}/image.jpg", server.url), &path, Some(10)).unwrap();
+
+        assert_eq!(fs::read(&path).unwrap(), b"0123456789");
+        assert!(!partial_path(&path).exists());
+        assert!(server.requests()[0].contains("wallpaper_changer/"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn resumes_a_body_that_ended_early() {
+        let dir = temp_dir("resume");
+        let server = MockServer::start_raw(vec![
+            response("200 OK", "Content-Length: 10\r\n", b"0123"),
+            response("206 Partial Content", "Content-Range: bytes 4-9/10\r\nContent-Length: 6\r\n", b"456789"),
+        ]);
+        let path = dir.join("image.jpg");
+
+        download(&format!("{}/image.jpg", server.url), &path, None).unwrap();
+
+        assert_eq!(fs::read(&path).unwrap(), b"0123456789");
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        assert!(!requests[0].to_lowercase().contains("range:"));
+        assert!(requests[1].to_lowercase().contains("range: bytes=4-"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn starts_over_when_the_range_is_ignored() {
+        let dir = temp_dir("ignored");
+        let server = MockServer::start_raw(vec![response("200 OK", "Content-Length: 10\r\n", b"0123456789")]);
+        let path = dir.join("image.jpg");
+        fs::write(partial_path(&path), b"xxxx").unwrap();
+
+        download(&format!("{}/image.jpg", server.url), &path, Some(10)).unwrap();
+
+        assert_eq!(fs::read(&path).unwrap(), b"0123456789");
+        assert!(server.requests()[0].to_lowercase().contains("range: bytes=4-"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn rejects_a_different_range() {
+        let dir = temp_dir("range");
+        let server = MockServer::start_raw(vec![
+            response("206 Partial Content", "Content-Range: bytes 0-9/10\r\nContent-Length: 10\r\n", b"0123456789"),
+        ]);
+        let path = dir.join("image.jpg");
+        fs::write(partial_path(&path), b"0123").unwrap();
+
+        let e = download(&format!("{}/image.jpg", server.url), &path, Some(10)).unwrap_err();
+
+        assert!(e.to_string().contains("different range"));
+        assert!(!partial_path(&path).exists());
+        assert!(!path.exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn unsatisfiable_range_means_a_smaller_file() {
+        let dir = temp_dir("416");
+        let server = MockServer::start_raw(vec![response("416 Range Not Satisfiable", "Content-Length: 0\r\n", b"")]);
+        let path = dir.join("image.jpg");
+        fs::write(partial_path(&path), b"01234").unwrap();
+
+        let e = download(&format!("{}/image.jpg", server.url), &path, Some(10)).unwrap_err();
+
+        assert!(e.to_string().contains("smaller than the expected 10 bytes"));
+        assert!(!partial_path(&path).exists());
+        assert_eq!(server.requests().len(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn rejects_a_larger_file() {
+        let dir = temp_dir("larger");
+        let server = MockServer::start_raw(vec![response("200 OK", "Content-Length: 12\r\n", b"0123456789ab")]);
+        let path = dir.join("image.jpg");
+
+        let e = download(&format!("{}/image.jpg", server.url), &path, Some(10)).unwrap_err();
+
+        assert!(e.to_string().contains("Downloaded 12 bytes"));
+        assert!(!partial_path(&path).exists());
+        assert!(!path.exists());
+        assert_eq!(server.requests().len(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn http_errors_are_not_retried() {
+        let dir = temp_dir("404");
+        let server = MockServer::start_raw(vec![
+            response("404 Not Found", "Content-Length: 0\r\n", b""),
+            response("200 OK", "Content-Length: 10\r\n", b"0123456789"),
+        ]);
+        let path = dir.join("image.jpg");
+
+        assert!(download(&format!("{}/image.jpg", server.url), &path, None).is_err());
+
+        assert_eq!(server.requests().len(), 1);
+        assert!(!path.exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn file_errors_are_not_retried() {
+        let dir = temp_dir("missing");
+        let server = MockServer::start_raw(vec![
+            response("200 OK", "Content-Length: 10\r\n", b"0123456789"),
+            response("200 OK", "Content-Length: 10\r\n", b"0123456789"),
+        ]);
+        // The .part file can't be created in a directory that doesn't exist
+        let path = dir.join("missing").join("image.jpg");
+
+        let e = download(&format!("{}/image.jpg", server.url), &path, None).unwrap_err();
+
+        assert!(e.downcast_ref::<io::Error>().is_some_and(|e| e.kind() == io::ErrorKind::NotFound));
+        assert_eq!(server.requests().len(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,9 +1,9 @@
-use std::char::MAX;
 use std::fs;
 use std::path::Path;
 
 pub mod image;
 
+#[allow(dead_code)] // Not called anywhere yet
 pub fn clear_temp_thumbnails(temp_folder: &str) -> std::io::Result<()> {
     const MAX_FILES: usize = 1000;
     const MAX_SIZE_BYTES: u64 = 500 * 1024 * 1024; // 500MB in bytes
@@ -22,12 +22,10 @@ pub fn clear_temp_thumbnails(temp_folder: &str) -> std::io::Result<()> {
     let mut file_count = 0;
     let mut total_size: u64 = 0;
 
-    for entry in entries {
-        if let Ok(entry) = entry {
-            if let Ok(metadata) = entry.metadata() {
-                file_count += 1;
-                total_size += metadata.len();
-            }
+    for entry in entries.flatten() {
+        if let Ok(metadata) = entry.metadata() {
+            file_count += 1;
+            total_size += metadata.len();
         }
     }
 
@@ -37,16 +35,12 @@ pub fn clear_temp_thumbnails(temp_folder: &str) -> std::io::Result<()> {
         println!("Files: {}, Total size: {} MB", file_count, total_size / (1024 * 1024));
 
         // Read directory again to remove files
-        for entry in fs::read_dir(path)? {
-            if let Ok(entry) = entry {
-                if let Ok(metadata) = entry.metadata() {
-                    if metadata.is_file() {
-                        if let Err(e) = fs::remove_file(entry.path()) {
-                            eprintln!("Failed to remove file {}: {}",
-                                      entry.path().display(), e);
-                        }
-                    }
-                }
+        for entry in fs::read_dir(path)?.flatten() {
+            if let Ok(metadata) = entry.metadata()
+                && metadata.is_file()
+                && let Err(e) = fs::remove_file(entry.path()) {
+                eprintln!("Failed to remove file {}: {}",
+                          entry.path().display(), e);
             }
         }
 
@@ -0,0 +1,181 @@
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use serde::{Deserialize, Serialize};
+use crate::models::wallpaper::SearchMeta;
+use crate::{config, library, scheduler, utils, wallpaper};
+
+// Every wallpaper that was set, oldest first, so earlier ones can be put back
+// Kept in history.json in the user data directory
+
+const HISTORY_FILE_NAME: &str = "history.json";
+const DEFAULT_LIMIT: usize = 500;
+const DEFAULT_PER_PAGE: usize = 24;
+
+static LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct HistoryEntry {
+    // Increases with every change, unlike the position it survives old entries being dropped
+    pub id: u64,
+    pub changed_at: u64,
+    // Provider and image id when the image is in the library
+    pub source: Option<String>,
+    pub image_id: Option<String>,
+    pub path: String,
+    // Monitor it was set on, None for all of them
+    pub output: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct History {
+    #[serde(default)]
+    pub entries: Vec<HistoryEntry>,
+    // Entry id on screen after /history/previous or /history/next, None means the latest one
+    #[serde(default)]
+    pub cursor: Option<u64>,
+    #[serde(default)]
+    pub next_id: u64,
+}
+
+pub fn get_history_path() -> PathBuf {
+    utils::get_user_data_directory().join(HISTORY_FILE_NAME)
+}
+
+pub fn load() -> History {
+    let _guard = LOCK.lock().unwrap();
+    utils::read_json_file(&get_history_path())
+}
+
+fn update<T, F: FnOnce(&mut History) -> T>(change: F) -> Result<T, Box<dyn Error + Send + Sync>> {
+    let _guard = LOCK.lock().unwrap();
+    let mut history: History = utils::read_json_file(&get_history_path());
+    let result = change(&mut history);
+    utils::write_json_file(&get_history_path(), &history)?;
+    Ok(result)
+}
+
+// Called by wallpaper::change, failing to write the history never fails the change itself
+pub fn record(path: &str, output: Option<&str>) {
+    let limit = config::load().history_limit.unwrap_or(DEFAULT_LIMIT);
+    let image = library::find_by_path(path);
+
+    let result = update(|history| {
+        history.entries.push(HistoryEntry {
+            id: history.next_id,
+            changed_at: scheduler::now(),
+            source: image.as_ref().map(|entry| entry.wallpaper.source.clone()),
+            image_id: image.as_ref().map(|entry| entry.wallpaper.id.clone()),
+            path: path.to_string(),
+            output: output.map(str::to_string),
+        });
+        history.next_id += 1;
+        // A new wallpaper ends any going back and forth
+        history.cursor = None;
+
+        if history.entries.len() > limit {
+            let excess = history.entries.len() - limit;
+            history.entries.drain(..excess);
+        }
+    });
+    if let Err(e) = result {
+        eprintln!("Failed to record wallpaper history: {}", e);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Direction {
+    Previous,
+    Next,
+}
+
+// Moves through the history and sets that wallpaper again, entries whose file is gone are skipped
+// None when there is nothing further in that direction
+pub fn step(direction: Direction) -> Result<Option<HistoryEntry>, String> {
+    let Some(entry) = target(direction) else {
+        return Ok(None);
+    };
+
+    // Setting the wallpaper runs desktop commands, so the lock is not held meanwhile
+    wallpaper::reapply(&entry.path, entry.output.as_deref())
+        .map_err(|e| format!("Failed to set {}: {}", entry.path, e))?;
+    // Another wallpaper may have been recorded in the meantime, the cursor is relative to the entries now
+    update(|history| {
+        let is_last = history.entries.last().is_none_or(|last| last.id == entry.id);
+        let still_there = history.entries.iter().any(|e| e.id == entry.id);
+        history.cursor = if is_last || !still_there { None } else { Some(entry.id) };
+    }).map_err(|e| format!("Failed to save history: {}", e))?;
+    Ok(Some(entry))
+}
+
+// The entry a step in that direction lands on
+fn target(direction: Direction) -> Option<HistoryEntry> {
+    let history = load();
+    let last = history.entries.len().checked_sub(1)?;
+    let mut position = history.cursor
+        .and_then(|cursor| history.entries.iter().position(|entry| entry.id == cursor))
+        .unwrap_or(last);
+
+    loop {
+        position = match direction {
+            Direction::Previous if position > 0 => position - 1,
+            Direction::Next if position < last => position + 1,
+            _ => return None,
+        };
+        let entry = &history.entries[position];
+        if Path::new(&entry.path).exists() {
+            return Some(entry.clone());
+        }
+        println!("History: {} no longer exists, skipping", entry.path);
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HistoryQuery {
+    #[serde(default = "default_page")]
+    pub page: usize,
+    pub per_page: Option<usize>,
+}
+
+fn default_page() -> usize { 1 }
+
+#[derive(Debug, Serialize)]
+pub struct HistoryPage {
+    pub data: Vec<HistoryEntry>,
+    pub meta: SearchMeta,
+    // Entry currently on screen
+    pub current: Option<u64>,
+}
+
+impl HistoryQuery {
+    // Newest first
+    pub fn run(&self) -> Result<HistoryPage, String> {
+        if self.page == 0 {
+            return Err("Invalid page '0', pages start at 1".to_string());
+        }
+        let per_page = self.per_page.unwrap_or(DEFAULT_PER_PAGE);
+        if !(1..=100).contains(&per_page) {
+            return Err(format!("Invalid per_page '{}', expected 1-100", per_page));
+        }
+
+        let history = load();
+        let total = history.entries.len();
+        let current = history.cursor.or(history.entries.last().map(|entry| entry.id));
+        let data = history.entries.into_iter().rev()
+            .skip(self.page.saturating_sub(1).saturating_mul(per_page))
+            .take(per_page)
+            .collect();
+        Ok(HistoryPage {
+            data,
+            meta: SearchMeta {
+                current_page: self.page as u32,
+                last_page: total.div_ceil(per_page).max(1) as u32,
+                per_page: per_page as u32,
+                total: total as u64,
+                query: String::new(),
+                seed: None,
+            },
+            current,
+        })
+    }
+}
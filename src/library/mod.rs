@@ -0,0 +1,346 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use crate::models::wallpaper::{SearchMeta, Wallpaper};
+use crate::{scheduler, utils};
+
+// Metadata for every downloaded image, keyed by "<source>:<id>"
+// Kept as one JSON file in the user data directory so details and local searches work offline
+
+const LIBRARY_FILE_NAME: &str = "library.json";
+const DEFAULT_PER_PAGE: usize = 24;
+
+// The API workers and the rotation task all write to the same file
+static LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LibraryEntry {
+    #[serde(flatten)]
+    pub wallpaper: Wallpaper,
+    // Where the original was saved
+    pub path: String,
+    pub downloaded_at: u64,
+    #[serde(default)]
+    pub favorite: bool,
+    // 1-5 stars
+    #[serde(default)]
+    pub rating: Option<u8>,
+}
+
+impl LibraryEntry {
+    // Relative chance of being picked by weighted rotation and "surprise me",
+    // unrated images count as 3 stars and favorites count double
+    pub fn weight(&self) -> f64 {
+        let stars = self.rating.unwrap_or(3).clamp(1, 5) as f64;
+        if self.favorite { stars * 2.0 } else { stars }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct Library {
+    #[serde(default)]
+    pub entries: BTreeMap<String, LibraryEntry>,
+}
+
+pub fn key(source: &str, id: &str) -> String {
+    format!("{}:{}", source, id)
+}
+
+pub fn get_library_path() -> PathBuf {
+    utils::get_user_data_directory().join(LIBRARY_FILE_NAME)
+}
+
+fn read() -> Library {
+    utils::read_json_file(&get_library_path())
+}
+
+fn write(library: &Library) -> Result<(), Box<dyn Error + Send + Sync>> {
+    utils::write_json_file(&get_library_path(), library)
+}
+
+pub fn load() -> Library {
+    let _guard = LOCK.lock().unwrap();
+    read()
+}
+
+// Loads, modifies and saves the library in one go
+pub fn update<T, F: FnOnce(&mut Library) -> T>(change: F) -> Result<T, Box<dyn Error + Send + Sync>> {
+    let _guard = LOCK.lock().unwrap();
+    let mut library = read();
+    let result = change(&mut library);
+    write(&library)?;
+    Ok(result)
+}
+
+// Adds or refreshes the entry for a downloaded image, keeping favorites/ratings and the richer metadata
+// when the new copy has less (search results don't include tags, the single image endpoints do)
+pub fn record(wallpaper: &Wallpaper, path: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+    update(|library| insert(library, wallpaper, path))
+}
+
+// Same as record for many images with a single write, e.g. a whole imported tag
+pub fn record_all(images: &[(Wallpaper, String)]) -> Result<(), Box<dyn Error + Send + Sync>> {
+    update(|library| {
+        for (wallpaper, path) in images {
+            insert(library, wallpaper, path);
+        }
+    })
+}
+
+fn insert(library: &mut Library, wallpaper: &Wallpaper, path: &str) {
+    let key = key(&wallpaper.source, &wallpaper.id);
+    let mut wallpaper = wallpaper.clone();
+    let (downloaded_at, favorite, rating) = match library.entries.get(&key) {
+        Some(existing) => {
+            if wallpaper.tags.is_empty() {
+                wallpaper.tags = existing.wallpaper.tags.clone();
+            }
+            (existing.downloaded_at, existing.favorite, existing.rating)
+        },
+        None => (scheduler::now(), false, None),
+    };
+    library.entries.insert(key, LibraryEntry { wallpaper, path: path.to_string(), downloaded_at, favorite, rating });
+}
+
+pub fn get(source: &str, id: &str) -> Option<LibraryEntry> {
+    load().entries.remove(&key(source, id))
+}
+
+pub fn find_by_path(path: &str) -> Option<LibraryEntry> {
+    load().entries.into_values().find(|entry| entry.path == path)
+}
+
+// None when the image isn't in the library
+pub fn set_favorite(source: &str, id: &str, favorite: bool) -> Result<Option<LibraryEntry>, Box<dyn Error + Send + Sync>> {
+    update(|library| library.entries.get_mut(&key(source, id)).map(|entry| {
+        entry.favorite = favorite;
+        entry.clone()
+    }))
+}
+
+// None clears the rating
+pub fn set_rating(source: &str, id: &str, rating: Option<u8>) -> Result<Option<LibraryEntry>, Box<dyn Error + Send + Sync>> {
+    update(|library| library.entries.get_mut(&key(source, id)).map(|entry| {
+        entry.rating = rating;
+        entry.clone()
+    }))
+}
+
+// Random order where higher weights tend to come first (Efraimidis-Spirakis), every item still appears once
+pub fn weighted_shuffle<T>(items: Vec<(T, f64)>) -> Vec<T> {
+    let mut rng = rand::rng();
+    let mut keyed: Vec<(f64, T)> = items.into_iter()
+        .map(|(item, weight)| (rng.random::<f64>().powf(1.0 / weight.max(0.01)), item))
+        .collect();
+    keyed.sort_by(|a, b| b.0.total_cmp(&a.0));
+    keyed.into_iter().map(|(_, item)| item).collect()
+}
+
+// Picks one entry at random, favourites and well rated images more often
+pub fn pick_weighted(entries: Vec<LibraryEntry>) -> Option<LibraryEntry> {
+    let weighted = entries.into_iter().map(|entry| {
+        let weight = entry.weight();
+        (entry, weight)
+    }).collect();
+    weighted_shuffle(weighted).into_iter().next()
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct LibraryQuery {
+    // Matches the id, tags, author and source URL
+    pub q: Option<String>,
+    pub source: Option<String>,
+    pub tag: Option<String>,
+    // Hex colour as used by Wallhaven, e.g. "#663399"
+    pub color: Option<String>,
+    pub min_width: Option<u32>,
+    pub min_height: Option<u32>,
+    pub favorite: Option<bool>,
+    pub min_rating: Option<u8>,
+    #[serde(default = "default_page")]
+    pub page: usize,
+    pub per_page: Option<usize>,
+}
+
+fn default_page() -> usize { 1 }
+
+#[derive(Debug, Serialize)]
+pub struct LibraryPage {
+    pub data: Vec<LibraryEntry>,
+    pub meta: SearchMeta,
+}
+
+impl LibraryQuery {
+    // Every entry passing the filters, ignoring paging
+    pub fn matching(&self) -> Vec<LibraryEntry> {
+        self.matching_in(load())
+    }
+
+    fn matching_in(&self, library: Library) -> Vec<LibraryEntry> {
+        library.entries.into_values()
+            .filter(|entry| self.matches(entry))
+            .collect()
+    }
+
+    fn matches(&self, entry: &LibraryEntry) -> bool {
+        let wallpaper = &entry.wallpaper;
+        let contains = |value: &str, needle: &str| value.to_lowercase().contains(needle);
+
+        if let Some(source) = self.source.as_deref().filter(|s| !s.trim().is_empty())
+            && !wallpaper.source.eq_ignore_ascii_case(source.trim()) {
+            return false;
+        }
+        if let Some(tag) = self.tag.as_deref().map(|t| t.trim().to_lowercase()).filter(|t| !t.is_empty())
+            && !wallpaper.tags.iter().any(|t| t.to_lowercase() == tag) {
+            return false;
+        }
+        if let Some(color) = self.color.as_deref().map(|c| c.trim().trim_start_matches('#').to_lowercase()).filter(|c| !c.is_empty())
+            && !wallpaper.colors.iter().any(|c| c.trim_start_matches('#').to_lowercase() == color) {
+            return false;
+        }
+        if self.favorite.is_some_and(|favorite| entry.favorite != favorite)
+            || self.min_rating.is_some_and(|min| entry.rating.unwrap_or(0) < min) {
+            return false;
+        }
+        if self.min_width.is_some_and(|min| wallpaper.width < min)
+            || self.min_height.is_some_and(|min| wallpaper.height < min) {
+            return false;
+        }
+        if let Some(q) = self.q.as_deref().map(|q| q.trim().to_lowercase()).filter(|q| !q.is_empty()) {
+            return contains(&wallpaper.id, &q)
+                || wallpaper.tags.iter().any(|t| contains(t, &q))
+                || wallpaper.author.as_deref().is_some_and(|a| contains(a, &q))
+                || wallpaper.source_url.as_deref().is_some_and(|s| contains(s, &q));
+        }
+        true
+    }
+
+    // Newest downloads first
+    pub fn run(&self) -> Result<LibraryPage, String> {
+        self.run_in(load())
+    }
+
+    fn run_in(&self, library: Library) -> Result<LibraryPage, String> {
+        if self.page == 0 {
+            return Err("Invalid page '0', pages start at 1".to_string());
+        }
+        let per_page = self.per_page.unwrap_or(DEFAULT_PER_PAGE);
+        if !(1..=100).contains(&per_page) {
+            return Err(format!("Invalid per_page '{}', expected 1-100", per_page));
+        }
+
+        let mut entries = self.matching_in(library);
+        entries.sort_by_key(|entry| std::cmp::Reverse(entry.downloaded_at));
+
+        let total = entries.len();
+        let data = entries.into_iter().skip(self.page.saturating_sub(1).saturating_mul(per_page)).take(per_page).collect();
+        Ok(LibraryPage {
+            data,
+            meta: SearchMeta {
+                current_page: self.page as u32,
+                last_page: total.div_ceil(per_page).max(1) as u32,
+                per_page: per_page as u32,
+                total: total as u64,
+                query: self.q.clone().unwrap_or_default(),
+                seed: None,
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(source: &str, id: &str, downloaded_at: u64) -> LibraryEntry {
+        LibraryEntry {
+            wallpaper: Wallpaper {
+                source: source.to_string(),
+                id: id.to_string(),
+                url: String::new(),
+                thumbnail_url: String::new(),
+                original_url: String::new(),
+                width: 1920,
+                height: 1080,
+                file_type: "image/jpeg".to_string(),
+                file_size: None,
+                author: None,
+                source_url: None,
+                tags: Vec::new(),
+                colors: Vec::new(),
+                purity: None,
+                category: None,
+                created_at: None,
+            },
+            path: format!("/downloads/{}-{}.jpg", source, id),
+            downloaded_at,
+            favorite: false,
+            rating: None,
+        }
+    }
+
+    fn library() -> Library {
+        let mut lake = entry("wallhaven", "1pvwjw", 100);
+        lake.wallpaper.tags = vec!["Lake".to_string(), "nature".to_string()];
+        lake.wallpaper.colors = vec!["#663399".to_string()];
+        lake.wallpaper.width = 3840;
+        lake.wallpaper.height = 2160;
+        lake.favorite = true;
+        lake.rating = Some(5);
+
+        let mut city = entry("unsplash", "Dwu85P9SOIk", 300);
+        city.wallpaper.author = Some("Joe Example".to_string());
+        city.wallpaper.tags = vec!["city".to_string()];
+        city.rating = Some(2);
+
+        let mut forest = entry("wallhaven", "zxk6qy", 200);
+        forest.wallpaper.source_url = Some("https://www.flickr.com/photos/forest".to_string());
+        forest.wallpaper.tags = vec!["nature".to_string()];
+
+        let mut library = Library::default();
+        for entry in [lake, city, forest] {
+            library.entries.insert(key(&entry.wallpaper.source, &entry.wallpaper.id), entry);
+        }
+        library
+    }
+
+    fn ids(query: LibraryQuery) -> Vec<String> {
+        query.run_in(library()).unwrap().data.into_iter().map(|entry| entry.wallpaper.id).collect()
+    }
+
+    #[test]
+    fn filters() {
+        // Newest download first
+        assert_eq!(ids(LibraryQuery { page: 1, ..Default::default() }), vec!["Dwu85P9SOIk", "zxk6qy", "1pvwjw"]);
+        assert_eq!(ids(LibraryQuery { page: 1, source: Some(" Wallhaven ".to_string()), ..Default::default() }), vec!["zxk6qy", "1pvwjw"]);
+        assert_eq!(ids(LibraryQuery { page: 1, tag: Some("NATURE".to_string()), ..Default::default() }), vec!["zxk6qy", "1pvwjw"]);
+        assert_eq!(ids(LibraryQuery { page: 1, color: Some("663399".to_string()), ..Default::default() }), vec!["1pvwjw"]);
+        assert_eq!(ids(LibraryQuery { page: 1, min_width: Some(2560), ..Default::default() }), vec!["1pvwjw"]);
+        assert_eq!(ids(LibraryQuery { page: 1, favorite: Some(false), ..Default::default() }), vec!["Dwu85P9SOIk", "zxk6qy"]);
+        // Unrated images count as 0 here
+        assert_eq!(ids(LibraryQuery { page: 1, min_rating: Some(2), ..Default::default() }), vec!["Dwu85P9SOIk", "1pvwjw"]);
+        // q looks at the id, tags, author and source URL
+        assert_eq!(ids(LibraryQuery { page: 1, q: Some("joe".to_string()), ..Default::default() }), vec!["Dwu85P9SOIk"]);
+        assert_eq!(ids(LibraryQuery { page: 1, q: Some("flickr".to_string()), ..Default::default() }), vec!["zxk6qy"]);
+        assert_eq!(ids(LibraryQuery { page: 1, q: Some("lake".to_string()), tag: Some("city".to_string()), ..Default::default() }), Vec::<String>::new());
+    }
+
+    #[test]
+    fn paging() {
+        let page = LibraryQuery { page: 2, per_page: Some(2), ..Default::default() }.run_in(library()).unwrap();
+        assert_eq!(page.data.len(), 1);
+        assert_eq!(page.data[0].wallpaper.id, "1pvwjw");
+        assert_eq!((page.meta.current_page, page.meta.last_page, page.meta.total), (2, 2, 3));
+
+        // Past the end is an empty page rather than an overflow
+        let page = LibraryQuery { page: usize::MAX, per_page: Some(100), ..Default::default() }.run_in(library()).unwrap();
+        assert!(page.data.is_empty());
+
+        assert!(LibraryQuery { page: 0, ..Default::default() }.run_in(library()).is_err());
+        assert!(LibraryQuery { page: 1, per_page: Some(101), ..Default::default() }.run_in(library()).is_err());
+        assert_eq!(LibraryQuery { page: 1, ..Default::default() }.run_in(Library::default()).unwrap().meta.last_page, 1);
+    }
+}
@@ -1,24 +1,97 @@
-use std::error::Error;
-use actix_web::{App, HttpServer};
+use actix_web::{web, App, HttpServer};
 
 mod wallpaper;
 mod models;
 mod download;
 mod utils;
+// Not used by the API yet, left as it was
+#[allow(dead_code, clippy::ptr_arg, clippy::zombie_processes)]
 mod file_manager;
 mod api;
+mod config;
+mod scheduler;
+mod sources;
+mod daily;
+mod library;
+mod history;
+mod state;
+mod collections;
+
+fn main() -> std::io::Result<()> {
+    let args: Vec<String> = std::env::args().collect();
+    // Daily mode blocks between checks, so it runs before (and instead of) the async runtime
+    if let Some(index) = args.iter().position(|arg| arg == utils::flags::DAILY) {
+        return run_daily(args.get(index + 1).filter(|arg| !arg.starts_with("--")), args.iter().any(|arg| arg == utils::flags::ONCE));
+    }
+    run_server()
+}
 
 #[actix_web::main]
-async fn main() -> std::io::Result<()> {
-    HttpServer::new(|| {
+async fn run_server() -> std::io::Result<()> {
+    if config::load().restore_on_startup {
+        tokio::task::spawn_blocking(state::restore);
+    }
+    tokio::spawn(state::watch());
+
+    // Created once so every worker shares the same rotation task
+    let scheduler = web::Data::new(scheduler::Scheduler::new());
+
+    HttpServer::new(move || {
         App::new()
+            .app_data(scheduler.clone())
             .service(api::search_theme)
             .service(api::change_wallpaper)
+            .service(api::similar_images)
+            .service(api::get_tag)
             .service(api::create_tag)
+            .service(api::rename_tag)
+            .service(api::delete_tag)
             .service(api::tag_image)
+            .service(api::untag_image)
+            .service(api::update_tag_images)
             .service(api::list_collections)
+            .service(api::export_tag)
+            .service(api::import_tag)
+            .service(api::list_wallhaven_collections)
+            .service(api::sync_tag)
+            .service(api::sync_status)
+            .service(api::unsync_tag)
+            .service(api::sync_all_tags)
+            .service(api::sync_all_status)
+            .service(api::get_account)
+            .service(api::update_account)
+            .service(api::start_rotation)
+            .service(api::stop_rotation)
+            .service(api::rotation_status)
+            .service(api::list_monitors)
+            .service(api::daily_wallpaper)
+            .service(api::search_library)
+            .service(api::get_library_entry)
+            .service(api::list_history)
+            .service(api::previous_wallpaper)
+            .service(api::next_wallpaper)
+            .service(api::current_wallpaper)
+            .service(api::set_favorite)
+            .service(api::set_rating)
+            .service(api::list_favorites)
+            .service(api::surprise_me)
     })
-        .bind(("127.0.0.1:8080"))?
+        .bind("127.0.0.1:8080")?
         .run()
         .await
-}
\ No newline at end of file
+}
+// Runs without the API server, for autostart entries or cron jobs
+fn run_daily(providers: Option<&String>, once: bool) -> std::io::Result<()> {
+    let providers = daily::parse_providers(providers.map(String::as_str))
+        .map_err(std::io::Error::other)?;
+    if !once {
+        daily::run_forever(&providers);
+    }
+
+    match daily::apply(&providers) {
+        Ok(Some(image)) => println!("{}", image.title.unwrap_or(image.date)),
+        Ok(None) => println!("No daily image to set today"),
+        Err(e) => return Err(std::io::Error::other(e.to_string()))
+    }
+    Ok(())
+}
@@ -0,0 +1,40 @@
+use serde::{ Deserialize, Serialize};
+
+// https://www.bing.com/HPImageArchive.aspx?format=js&idx=0&n=1&mkt=en-US
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BingArchiveResponse {
+    pub images: Vec<BingImage>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BingImage {
+    // Day the image is shown, e.g. "20240131"
+    pub startdate: String,
+    // Relative path of the 1920x1080 version
+    pub url: String,
+    // Relative path without the size suffix, e.g. "/th?id=OHR.SomePlace_EN-US123"
+    pub urlbase: String,
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub copyright: Option<String>,
+    #[serde(default)]
+    pub copyrightlink: Option<String>,
+}
+
+// https://api.nasa.gov/planetary/apod?api_key=DEMO_KEY
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ApodResponse {
+    // e.g. "2024-01-31"
+    pub date: String,
+    pub title: String,
+    #[serde(default)]
+    pub explanation: Option<String>,
+    // "image" or "video"
+    pub media_type: String,
+    pub url: String,
+    #[serde(default)]
+    pub hdurl: Option<String>,
+    #[serde(default)]
+    pub copyright: Option<String>,
+}
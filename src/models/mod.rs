@@ -1 +1,6 @@
-pub mod wallhaven;
\ No newline at end of file
+pub mod daily;
+pub mod pexels;
+pub mod reddit;
+pub mod unsplash;
+pub mod wallhaven;
+pub mod wallpaper;
@@ -0,0 +1,87 @@
+use serde::{ Deserialize, Serialize};
+use crate::models::wallpaper::{SearchMeta, SearchPage, Wallpaper};
+
+// https://www.pexels.com/api/documentation/#photos-search
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PexelsSearchResponse {
+    pub page: u32,
+    pub per_page: u32,
+    pub total_results: u64,
+    pub photos: Vec<PexelsPhoto>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PexelsPhoto {
+    pub id: u64,
+    pub width: u32,
+    pub height: u32,
+    // Page for the photo on pexels.com
+    pub url: String,
+    pub photographer: String,
+    #[serde(default)]
+    pub photographer_url: Option<String>,
+    #[serde(default)]
+    pub avg_color: Option<String>,
+    #[serde(default)]
+    pub alt: Option<String>,
+    pub src: PexelsSources,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PexelsSources {
+    pub original: String,
+    pub large2x: String,
+    pub large: String,
+    pub medium: String,
+    pub small: String,
+    pub portrait: String,
+    pub landscape: String,
+    pub tiny: String,
+}
+
+impl From<PexelsPhoto> for Wallpaper {
+    fn from(photo: PexelsPhoto) -> Self {
+        // Originals are whatever the photographer uploaded, mostly JPEG but sometimes PNG
+        let file_type = if photo.src.original.to_lowercase().ends_with(".png") {
+            "image/png"
+        } else {
+            "image/jpeg"
+        };
+        Wallpaper {
+            source: "pexels".to_string(),
+            id: photo.id.to_string(),
+            url: photo.url,
+            thumbnail_url: photo.src.medium,
+            original_url: photo.src.original,
+            width: photo.width,
+            height: photo.height,
+            file_type: file_type.to_string(),
+            file_size: None,
+            author: Some(photo.photographer),
+            source_url: photo.photographer_url,
+            tags: Vec::new(),
+            colors: photo.avg_color.into_iter().collect(),
+            purity: None,
+            category: None,
+            created_at: None,
+        }
+    }
+}
+
+impl PexelsSearchResponse {
+    pub fn into_page(self, query: &str) -> SearchPage {
+        let per_page = self.per_page.max(1);
+        SearchPage {
+            data: self.photos.into_iter().map(Wallpaper::from).collect(),
+            meta: SearchMeta {
+                current_page: self.page,
+                last_page: self.total_results.div_ceil(per_page as u64).max(1) as u32,
+                per_page,
+                total: self.total_results,
+                query: query.to_string(),
+                seed: None,
+            },
+        }
+    }
+}
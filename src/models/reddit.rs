@@ -0,0 +1,282 @@
+use serde::{ Deserialize, Serialize};
+use crate::models::wallpaper::Wallpaper;
+
+// Public listing JSON, e.g. https://www.reddit.com/r/wallpapers/top.json?t=week&raw_json=1
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RedditListing {
+    pub data: RedditListingData,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RedditListingData {
+    // Token for the next page, None on the last one
+    #[serde(default)]
+    pub after: Option<String>,
+    pub children: Vec<RedditChild>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RedditChild {
+    pub data: RedditPost,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RedditPost {
+    pub id: String,
+    pub title: String,
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub url_overridden_by_dest: Option<String>,
+    pub permalink: String,
+    #[serde(default)]
+    pub author: Option<String>,
+    #[serde(default)]
+    pub subreddit: Option<String>,
+    #[serde(default)]
+    pub over_18: bool,
+    #[serde(default)]
+    pub is_video: bool,
+    #[serde(default)]
+    pub is_gallery: Option<bool>,
+    #[serde(default)]
+    pub created_utc: Option<f64>,
+    #[serde(default)]
+    pub preview: Option<RedditPreview>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RedditPreview {
+    pub images: Vec<RedditPreviewImage>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RedditPreviewImage {
+    pub source: RedditImage,
+    #[serde(default)]
+    pub resolutions: Vec<RedditImage>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RedditImage {
+    pub url: String,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl RedditPost {
+    // Only posts linking straight to an image are usable, text posts, galleries and videos are skipped
+    pub fn to_wallpaper(&self) -> Option<Wallpaper> {
+        if self.is_video || self.is_gallery.unwrap_or(false) {
+            return None;
+        }
+        let link = self.url_overridden_by_dest.as_deref().or(self.url.as_deref())?;
+        let original_url = direct_image_url(link)?;
+        let file_type = mime_from_url(&original_url)?;
+
+        let preview = self.preview.as_ref().and_then(|preview| preview.images.first());
+        let (width, height) = match preview {
+            Some(image) => (image.source.width, image.source.height),
+            None => resolution_from_title(&self.title).unwrap_or((0, 0)),
+        };
+        // Smallest preview that is still at least as wide as a Wallhaven thumbnail
+        let thumbnail_url = preview
+            .and_then(|image| image.resolutions.iter().find(|r| r.width >= 300).or(image.resolutions.last()))
+            .map(|r| r.url.clone())
+            .unwrap_or_else(|| original_url.clone());
+
+        Some(Wallpaper {
+            source: "reddit".to_string(),
+            id: self.id.clone(),
+            url: format!("https://www.reddit.com{}", self.permalink),
+            thumbnail_url,
+            original_url,
+            width,
+            height,
+            file_type: file_type.to_string(),
+            file_size: None,
+            author: self.author.clone(),
+            source_url: Some(link.to_string()),
+            tags: self.subreddit.iter().map(|subreddit| format!("r/{}", subreddit)).collect(),
+            colors: Vec::new(),
+            purity: Some(if self.over_18 { "nsfw" } else { "sfw" }.to_string()),
+            category: None,
+            created_at: self.created_utc.map(|created| (created as u64).to_string()),
+        })
+    }
+}
+
+// i.redd.it and i.imgur.com links are used as is, imgur.com/<id> pages are turned into the direct link
+fn direct_image_url(link: &str) -> Option<String> {
+    let without_query = link.split(['?', '#']).next().unwrap_or(link);
+    if mime_from_url(without_query).is_some() {
+        return Some(link.to_string());
+    }
+
+    let path = without_query
+        .strip_prefix("https://imgur.com/")
+        .or_else(|| without_query.strip_prefix("http://imgur.com/"))
+        .or_else(|| without_query.strip_prefix("https://m.imgur.com/"))?;
+    // Albums and galleries hold several images
+    if path.is_empty() || path.contains('/') {
+        return None;
+    }
+    Some(format!("https://i.imgur.com/{}.jpg", path))
+}
+
+fn mime_from_url(url: &str) -> Option<&'static str> {
+    let path = url.split(['?', '#']).next().unwrap_or(url).to_lowercase();
+    let extension = path.rsplit('.').next()?;
+    match extension {
+        "jpg" | "jpeg" => Some("image/jpeg"),
+        "png" => Some("image/png"),
+        "webp" => Some("image/webp"),
+        _ => None
+    }
+}
+
+// Wallpaper subreddits ask for the size in the title, e.g. "Lake at dusk [3840x2160]" or "(2560 × 1440)"
+fn resolution_from_title(title: &str) -> Option<(u32, u32)> {
+    let chars: Vec<char> = title.chars().collect();
+    for (i, c) in chars.iter().enumerate() {
+        if !matches!(c, 'x' | 'X' | '×') {
+            continue;
+        }
+        let before: String = chars[..i].iter().rev()
+            .skip_while(|c| c.is_whitespace())
+            .take_while(|c| c.is_ascii_digit())
+            .collect::<Vec<&char>>()
+            .into_iter().rev().collect();
+        let after: String = chars[i + 1..].iter()
+            .skip_while(|c| c.is_whitespace())
+            .take_while(|c| c.is_ascii_digit())
+            .collect();
+        if let (Ok(width), Ok(height)) = (before.parse::<u32>(), after.parse::<u32>())
+            && width >= 100 && height >= 100 {
+            return Some((width, height));
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Trimmed down from https://www.reddit.com/r/wallpapers/hot.json?raw_json=1
+    const LISTING: &str = r#"{
+        "data": {
+            "after": "t3_1c0xyz9",
+            "children": [
+                { "data": {
+                    "id": "1c0abcd",
+                    "title": "Lake at dusk [3840x2160]",
+                    "url": "https://i.redd.it/a1b2c3d4.jpg",
+                    "url_overridden_by_dest": "https://i.redd.it/a1b2c3d4.jpg",
+                    "permalink": "/r/wallpapers/comments/1c0abcd/lake_at_dusk_3840x2160/",
+                    "author": "someone",
+                    "subreddit": "wallpapers",
+                    "over_18": false,
+                    "is_video": false,
+                    "created_utc": 1712345678.0,
+                    "preview": { "images": [{
+                        "source": { "url": "https://preview.redd.it/a1b2c3d4.jpg?auto=webp&s=1", "width": 3840, "height": 2160 },
+                        "resolutions": [
+                            { "url": "https://preview.redd.it/a1b2c3d4.jpg?width=108&s=2", "width": 108, "height": 60 },
+                            { "url": "https://preview.redd.it/a1b2c3d4.jpg?width=320&s=3", "width": 320, "height": 180 },
+                            { "url": "https://preview.redd.it/a1b2c3d4.jpg?width=640&s=4", "width": 640, "height": 360 }
+                        ]
+                    }]}
+                }},
+                { "data": {
+                    "id": "1c0efgh",
+                    "title": "Mountains (2560 × 1440)",
+                    "url": "https://imgur.com/AbCdEf1",
+                    "permalink": "/r/wallpapers/comments/1c0efgh/mountains/",
+                    "subreddit": "wallpapers",
+                    "over_18": true
+                }},
+                { "data": {
+                    "id": "1c0ijkl",
+                    "title": "My setup, 3 images",
+                    "url": "https://www.reddit.com/gallery/1c0ijkl",
+                    "permalink": "/r/wallpapers/comments/1c0ijkl/my_setup/",
+                    "is_gallery": true
+                }},
+                { "data": {
+                    "id": "1c0mnop",
+                    "title": "Timelapse",
+                    "url": "https://v.redd.it/qrstuv",
+                    "permalink": "/r/wallpapers/comments/1c0mnop/timelapse/",
+                    "is_video": true
+                }},
+                { "data": {
+                    "id": "1c0qrst",
+                    "title": "Weekly wallpaper request thread",
+                    "url": "https://www.reddit.com/r/wallpapers/comments/1c0qrst/weekly/",
+                    "permalink": "/r/wallpapers/comments/1c0qrst/weekly/"
+                }}
+            ]
+        }
+    }"#;
+
+    fn posts() -> Vec<RedditPost> {
+        let listing: RedditListing = serde_json::from_str(LISTING).unwrap();
+        listing.data.children.into_iter().map(|child| child.data).collect()
+    }
+
+    #[test]
+    fn image_post() {
+        let wallpaper = posts()[0].to_wallpaper().unwrap();
+        assert_eq!(wallpaper.id, "1c0abcd");
+        assert_eq!(wallpaper.url, "https://www.reddit.com/r/wallpapers/comments/1c0abcd/lake_at_dusk_3840x2160/");
+        assert_eq!(wallpaper.original_url, "https://i.redd.it/a1b2c3d4.jpg");
+        // The first preview at least 300 wide
+        assert_eq!(wallpaper.thumbnail_url, "https://preview.redd.it/a1b2c3d4.jpg?width=320&s=3");
+        assert_eq!((wallpaper.width, wallpaper.height), (3840, 2160));
+        assert_eq!(wallpaper.file_type, "image/jpeg");
+        assert_eq!(wallpaper.tags, vec!["r/wallpapers"]);
+        assert_eq!(wallpaper.purity.as_deref(), Some("sfw"));
+        assert_eq!(wallpaper.created_at.as_deref(), Some("1712345678"));
+    }
+
+    #[test]
+    fn imgur_post_without_preview() {
+        let wallpaper = posts()[1].to_wallpaper().unwrap();
+        assert_eq!(wallpaper.original_url, "https://i.imgur.com/AbCdEf1.jpg");
+        assert_eq!(wallpaper.thumbnail_url, wallpaper.original_url);
+        // No preview, so the size comes from the title
+        assert_eq!((wallpaper.width, wallpaper.height), (2560, 1440));
+        assert_eq!(wallpaper.purity.as_deref(), Some("nsfw"));
+    }
+
+    #[test]
+    fn gallery_video_and_text_posts_are_skipped() {
+        let posts = posts();
+        assert!(posts[2].to_wallpaper().is_none());
+        assert!(posts[3].to_wallpaper().is_none());
+        assert!(posts[4].to_wallpaper().is_none());
+    }
+
+    #[test]
+    fn direct_image_urls() {
+        assert_eq!(direct_image_url("https://i.redd.it/abc.PNG").as_deref(), Some("https://i.redd.it/abc.PNG"));
+        assert_eq!(direct_image_url("https://i.imgur.com/abc.webp?1").as_deref(), Some("https://i.imgur.com/abc.webp?1"));
+        assert_eq!(direct_image_url("https://m.imgur.com/abc").as_deref(), Some("https://i.imgur.com/abc.jpg"));
+        assert_eq!(direct_image_url("https://imgur.com/a/abc"), None);
+        assert_eq!(direct_image_url("https://imgur.com/gallery/abc"), None);
+        assert_eq!(direct_image_url("https://i.redd.it/abc.gif"), None);
+        assert_eq!(direct_image_url("https://example.com/page"), None);
+    }
+
+    #[test]
+    fn resolutions_in_titles() {
+        assert_eq!(resolution_from_title("Lake at dusk [3840x2160]"), Some((3840, 2160)));
+        assert_eq!(resolution_from_title("Mountains (2560 × 1440) OC"), Some((2560, 1440)));
+        assert_eq!(resolution_from_title("City [1920 X 1080]"), Some((1920, 1080)));
+        // Too small to be a resolution
+        assert_eq!(resolution_from_title("Made 2x faster in 10x10 steps"), None);
+        assert_eq!(resolution_from_title("Exactly what it says"), None);
+    }
+}
@@ -0,0 +1,101 @@
+use serde::{ Deserialize, Serialize};
+use crate::models::wallpaper::{SearchMeta, SearchPage, Wallpaper};
+
+// https://unsplash.com/documentation#search-photos
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UnsplashSearchResponse {
+    pub total: u64,
+    pub total_pages: u32,
+    pub results: Vec<UnsplashPhoto>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UnsplashPhoto {
+    pub id: String,
+    #[serde(default)]
+    pub created_at: Option<String>,
+    pub width: u32,
+    pub height: u32,
+    // Average colour as a hex string, e.g. "#60544D"
+    #[serde(default)]
+    pub color: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub alt_description: Option<String>,
+    pub urls: UnsplashUrls,
+    pub links: UnsplashLinks,
+    pub user: UnsplashUser,
+    // Only included when fetching a single photo
+    #[serde(default)]
+    pub tags: Option<Vec<UnsplashTag>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UnsplashUrls {
+    pub raw: String,
+    pub full: String,
+    pub regular: String,
+    pub small: String,
+    pub thumb: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UnsplashLinks {
+    pub html: String,
+    #[serde(default)]
+    pub download_location: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UnsplashUser {
+    pub username: String,
+    #[serde(default)]
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UnsplashTag {
+    pub title: String,
+}
+
+impl From<UnsplashPhoto> for Wallpaper {
+    fn from(photo: UnsplashPhoto) -> Self {
+        Wallpaper {
+            source: "unsplash".to_string(),
+            id: photo.id,
+            url: photo.links.html,
+            thumbnail_url: photo.urls.small,
+            // "full" is the original size as a high quality JPEG
+            original_url: photo.urls.full,
+            width: photo.width,
+            height: photo.height,
+            file_type: "image/jpeg".to_string(),
+            file_size: None,
+            author: Some(photo.user.name.unwrap_or(photo.user.username)),
+            source_url: None,
+            tags: photo.tags.unwrap_or_default().into_iter().map(|tag| tag.title).collect(),
+            colors: photo.color.into_iter().collect(),
+            purity: None,
+            category: None,
+            created_at: photo.created_at,
+        }
+    }
+}
+
+impl UnsplashSearchResponse {
+    pub fn into_page(self, query: &str, page: u16, per_page: u32) -> SearchPage {
+        SearchPage {
+            data: self.results.into_iter().map(Wallpaper::from).collect(),
+            meta: SearchMeta {
+                current_page: page as u32,
+                last_page: self.total_pages.max(1),
+                per_page,
+                total: self.total,
+                query: query.to_string(),
+                seed: None,
+            },
+        }
+    }
+}
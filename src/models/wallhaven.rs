@@ -1,4 +1,6 @@
+use std::str::FromStr;
 use serde::{ Deserialize, Serialize};
+use crate::models::wallpaper::{SearchMeta, SearchPage, Wallpaper};
 
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -22,11 +24,19 @@ pub struct WHDirectModel {
 }
 
 
+// Response of /api/v1/tag/{id}
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WHTagResponse {
+    pub data: WHTag,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct WHTag {
     pub id: i32,
     pub name: String,
-    pub alias: String,
+    // Comma separated alternative names, can be empty
+    #[serde(default)]
+    pub alias: Option<String>,
     pub category_id: i32,
     pub category: String,
     pub purity: String,
@@ -86,4 +96,612 @@ pub struct WHImageThumbnailImageData {
     large: String,
     original: String,
     pub small: String,
-}
\ No newline at end of file
+}
+
+impl From<WHImageData> for Wallpaper {
+    fn from(image: WHImageData) -> Self {
+        Wallpaper {
+            source: "wallhaven".to_string(),
+            id: image.id,
+            url: image.url,
+            thumbnail_url: image.thumbs.small,
+            original_url: image.path,
+            width: image.dimension_x.max(0) as u32,
+            height: image.dimension_y.max(0) as u32,
+            file_type: image.file_type,
+            file_size: u64::try_from(image.file_size).ok(),
+            author: image.uploader.map(|uploader| uploader.username),
+            source_url: Some(image.source).filter(|source| !source.is_empty()),
+            tags: image.tags.unwrap_or_default().into_iter().map(|tag| tag.name).collect(),
+            colors: image.colors,
+            purity: Some(image.purity),
+            category: Some(image.category),
+            created_at: Some(image.created_at),
+        }
+    }
+}
+
+impl From<WHSearchResponse> for SearchPage {
+    fn from(response: WHSearchResponse) -> Self {
+        SearchPage {
+            data: response.data.into_iter().map(Wallpaper::from).collect(),
+            meta: SearchMeta {
+                current_page: response.meta.current_page.max(0) as u32,
+                last_page: response.meta.last_page.max(0) as u32,
+                per_page: response.meta.per_page.max(0) as u32,
+                total: response.meta.total,
+                query: response.meta.query,
+                seed: response.meta.seed,
+            },
+        }
+    }
+}
+
+// Collections of a user from /api/v1/collections/{username}, or your own (private ones too) from /api/v1/collections
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WHCollectionsResponse {
+    pub data: Vec<WHCollection>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WHCollection {
+    pub id: u64,
+    pub label: String,
+    pub views: u64,
+    // 1 for public, 0 for private
+    pub public: u8,
+    pub count: u64,
+}
+
+// Images in a collection from /api/v1/collections/{username}/{id}, same items as a search but the meta has no query
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WHCollectionListingResponse {
+    pub data: Vec<WHImageData>,
+    pub meta: WHCollectionListingMeta,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WHCollectionListingMeta {
+    pub current_page: i32,
+    pub last_page: i32,
+    pub total: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WHUserSettingsResponse {
+    pub data: WHUserSettings,
+}
+
+// Account defaults returned by /api/v1/settings (requires an API key)
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WHUserSettings {
+    pub thumb_size: String,
+    pub per_page: String,
+    pub purity: Vec<String>,
+    pub categories: Vec<String>,
+    pub resolutions: Vec<String>,
+    pub aspect_ratios: Vec<String>,
+    pub toplist_range: String,
+    #[serde(default)]
+    pub tag_blacklist: Vec<String>,
+    #[serde(default)]
+    pub user_blacklist: Vec<String>,
+}
+
+
+// Search query
+// Typed version of the parameters accepted by https://wallhaven.cc/api/v1/search
+// Every field is validated when parsed, so a built query is always safe to send.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WHCategories {
+    pub general: bool,
+    pub anime: bool,
+    pub people: bool,
+}
+
+impl WHCategories {
+    // Wallhaven expects a 3 character bit string, e.g. "101" = general + people
+    pub fn as_param(&self) -> String {
+        bits(&[self.general, self.anime, self.people])
+    }
+}
+
+impl FromStr for WHCategories {
+    type Err = String;
+
+    // Accepts either a bit string ("110") or a comma separated list ("general,anime")
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let [general, anime, people] = parse_flags(value, &["general", "anime", "people"], "categories")?;
+        Ok(WHCategories { general, anime, people })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WHPurity {
+    pub sfw: bool,
+    pub sketchy: bool,
+    pub nsfw: bool,
+}
+
+impl WHPurity {
+    pub fn as_param(&self) -> String {
+        bits(&[self.sfw, self.sketchy, self.nsfw])
+    }
+}
+
+impl FromStr for WHPurity {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let [sfw, sketchy, nsfw] = parse_flags(value, &["sfw", "sketchy", "nsfw"], "purity")?;
+        Ok(WHPurity { sfw, sketchy, nsfw })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WHSorting {
+    DateAdded,
+    Relevance,
+    Random,
+    Views,
+    Favorites,
+    Toplist,
+}
+
+impl WHSorting {
+    pub fn as_param(&self) -> &'static str {
+        match self {
+            WHSorting::DateAdded => "date_added",
+            WHSorting::Relevance => "relevance",
+            WHSorting::Random => "random",
+            WHSorting::Views => "views",
+            WHSorting::Favorites => "favorites",
+            WHSorting::Toplist => "toplist",
+        }
+    }
+}
+
+impl FromStr for WHSorting {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_lowercase().as_str() {
+            "date_added" => Ok(WHSorting::DateAdded),
+            "relevance" => Ok(WHSorting::Relevance),
+            "random" => Ok(WHSorting::Random),
+            "views" => Ok(WHSorting::Views),
+            "favorites" => Ok(WHSorting::Favorites),
+            "toplist" => Ok(WHSorting::Toplist),
+            other => Err(format!("Invalid sorting '{}', expected one of date_added, relevance, random, views, favorites, toplist", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WHOrder {
+    Desc,
+    Asc,
+}
+
+impl WHOrder {
+    pub fn as_param(&self) -> &'static str {
+        match self {
+            WHOrder::Desc => "desc",
+            WHOrder::Asc => "asc",
+        }
+    }
+}
+
+impl FromStr for WHOrder {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_lowercase().as_str() {
+            "desc" => Ok(WHOrder::Desc),
+            "asc" => Ok(WHOrder::Asc),
+            other => Err(format!("Invalid order '{}', expected desc or asc", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WHTopRange {
+    OneDay,
+    ThreeDays,
+    OneWeek,
+    OneMonth,
+    ThreeMonths,
+    SixMonths,
+    OneYear,
+}
+
+impl WHTopRange {
+    pub fn as_param(&self) -> &'static str {
+        match self {
+            WHTopRange::OneDay => "1d",
+            WHTopRange::ThreeDays => "3d",
+            WHTopRange::OneWeek => "1w",
+            WHTopRange::OneMonth => "1M",
+            WHTopRange::ThreeMonths => "3M",
+            WHTopRange::SixMonths => "6M",
+            WHTopRange::OneYear => "1y",
+        }
+    }
+}
+
+impl FromStr for WHTopRange {
+    type Err = String;
+
+    // Case matters here: "1m" is not a valid range, "1M" (one month) is
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim() {
+            "1d" => Ok(WHTopRange::OneDay),
+            "3d" => Ok(WHTopRange::ThreeDays),
+            "1w" => Ok(WHTopRange::OneWeek),
+            "1M" => Ok(WHTopRange::OneMonth),
+            "3M" => Ok(WHTopRange::ThreeMonths),
+            "6M" => Ok(WHTopRange::SixMonths),
+            "1y" => Ok(WHTopRange::OneYear),
+            other => Err(format!("Invalid topRange '{}', expected one of 1d, 3d, 1w, 1M, 3M, 6M, 1y", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WHResolution {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl WHResolution {
+    pub fn as_param(&self) -> String {
+        format!("{}x{}", self.width, self.height)
+    }
+}
+
+impl FromStr for WHResolution {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (width, height) = parse_pair(value)
+            .ok_or_else(|| format!("Invalid resolution '{}', expected WIDTHxHEIGHT e.g. 1920x1080", value))?;
+        Ok(WHResolution { width, height })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum WHRatio {
+    Landscape,
+    Portrait,
+    Exact(u32, u32),
+}
+
+impl WHRatio {
+    pub fn as_param(&self) -> String {
+        match self {
+            WHRatio::Landscape => "landscape".to_string(),
+            WHRatio::Portrait => "portrait".to_string(),
+            WHRatio::Exact(w, h) => format!("{}x{}", w, h),
+        }
+    }
+}
+
+impl FromStr for WHRatio {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_lowercase().as_str() {
+            "landscape" => Ok(WHRatio::Landscape),
+            "portrait" => Ok(WHRatio::Portrait),
+            other => parse_pair(other)
+                .map(|(w, h)| WHRatio::Exact(w, h))
+                .ok_or_else(|| format!("Invalid ratio '{}', expected landscape, portrait or WxH e.g. 16x9", value)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WHSearchQuery {
+    pub q: String,
+    pub categories: Option<WHCategories>,
+    pub purity: Option<WHPurity>,
+    pub sorting: Option<WHSorting>,
+    pub order: Option<WHOrder>,
+    pub top_range: Option<WHTopRange>,
+    pub atleast: Option<WHResolution>,
+    pub resolutions: Vec<WHResolution>,
+    pub ratios: Vec<WHRatio>,
+    pub colors: Option<String>,
+    pub page: u16,
+    pub seed: Option<String>,
+}
+
+impl WHSearchQuery {
+    pub fn new(q: &str, page: u16) -> Self {
+        WHSearchQuery {
+            q: q.trim().to_string(),
+            categories: None,
+            purity: None,
+            sorting: None,
+            order: None,
+            top_range: None,
+            atleast: None,
+            resolutions: Vec::new(),
+            ratios: Vec::new(),
+            colors: None,
+            page,
+            seed: None,
+        }
+    }
+
+    // Checks the combinations that Wallhaven would otherwise silently ignore
+    pub fn validate(&self) -> Result<(), String> {
+        if self.page == 0 {
+            return Err("Invalid page '0', pages start at 1".to_string());
+        }
+        if self.top_range.is_some() && self.sorting.is_some_and(|s| s != WHSorting::Toplist) {
+            return Err("topRange can only be used with sorting=toplist".to_string());
+        }
+        if let Some(color) = &self.colors
+            && (color.len() != 6 || !color.chars().all(|c| c.is_ascii_hexdigit())) {
+            return Err(format!("Invalid colors '{}', expected a hex colour e.g. 660000", color));
+        }
+        if let Some(seed) = &self.seed
+            && (seed.len() != 6 || !seed.chars().all(|c| c.is_ascii_alphanumeric())) {
+            return Err(format!("Invalid seed '{}', expected 6 alphanumeric characters", seed));
+        }
+        Ok(())
+    }
+
+    // Returns the (key, value) pairs in the form Wallhaven expects, not yet URL encoded
+    pub fn to_query_pairs(&self) -> Vec<(&'static str, String)> {
+        let mut pairs: Vec<(&'static str, String)> = vec![("q", self.q.clone())];
+        if let Some(categories) = &self.categories {
+            pairs.push(("categories", categories.as_param()));
+        }
+        if let Some(purity) = &self.purity {
+            pairs.push(("purity", purity.as_param()));
+        }
+        // topRange on its own implies the toplist
+        let sorting = match (self.sorting, self.top_range) {
+            (None, Some(_)) => Some(WHSorting::Toplist),
+            (sorting, _) => sorting,
+        };
+        if let Some(sorting) = sorting {
+            pairs.push(("sorting", sorting.as_param().to_string()));
+        }
+        if let Some(order) = &self.order {
+            pairs.push(("order", order.as_param().to_string()));
+        }
+        if let Some(top_range) = &self.top_range {
+            pairs.push(("topRange", top_range.as_param().to_string()));
+        }
+        if let Some(atleast) = &self.atleast {
+            pairs.push(("atleast", atleast.as_param()));
+        }
+        if !self.resolutions.is_empty() {
+            let list: Vec<String> = self.resolutions.iter().map(|r| r.as_param()).collect();
+            pairs.push(("resolutions", list.join(",")));
+        }
+        if !self.ratios.is_empty() {
+            let list: Vec<String> = self.ratios.iter().map(|r| r.as_param()).collect();
+            pairs.push(("ratios", list.join(",")));
+        }
+        if let Some(colors) = &self.colors {
+            pairs.push(("colors", colors.to_lowercase()));
+        }
+        pairs.push(("page", self.page.to_string()));
+        if let Some(seed) = &self.seed {
+            pairs.push(("seed", seed.clone()));
+        }
+        pairs
+    }
+}
+
+// Raw search parameters as sent to GET /search, names match the Wallhaven API
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WHSearchParams {
+    #[serde(default)]
+    pub topic: String,
+    #[serde(default = "default_page")]
+    pub page: u16,
+    pub categories: Option<String>,
+    pub purity: Option<String>,
+    pub sorting: Option<String>,
+    pub order: Option<String>,
+    #[serde(rename = "topRange")]
+    pub top_range: Option<String>,
+    pub atleast: Option<String>,
+    pub resolutions: Option<String>,
+    pub ratios: Option<String>,
+    pub colors: Option<String>,
+    pub seed: Option<String>,
+}
+
+fn default_page() -> u16 { 1 }
+
+impl WHSearchParams {
+    // Validates the raw query string values into a typed Wallhaven search query
+    pub fn to_query(&self) -> Result<WHSearchQuery, String> {
+        let mut query = WHSearchQuery::new(&self.topic, self.page);
+        query.categories = self.categories.as_deref().map(str::parse).transpose()?;
+        query.purity = self.purity.as_deref().map(str::parse).transpose()?;
+        query.sorting = self.sorting.as_deref().map(str::parse).transpose()?;
+        query.order = self.order.as_deref().map(str::parse).transpose()?;
+        query.top_range = self.top_range.as_deref().map(str::parse).transpose()?;
+        query.atleast = self.atleast.as_deref().map(str::parse).transpose()?;
+        query.resolutions = parse_list(self.resolutions.as_deref())?;
+        query.ratios = parse_list(self.ratios.as_deref())?;
+        query.colors = self.colors.as_deref().map(|c| c.trim().trim_start_matches('#').to_string());
+        query.seed = self.seed.as_deref().map(|s| s.trim().to_string());
+        query.validate()?;
+        Ok(query)
+    }
+}
+
+fn parse_list<T: FromStr<Err = String>>(value: Option<&str>) -> Result<Vec<T>, String> {
+    match value {
+        Some(list) => list.split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(str::parse)
+            .collect(),
+        None => Ok(Vec::new()),
+    }
+}
+
+fn bits(flags: &[bool]) -> String {
+    flags.iter().map(|on| if *on { '1' } else { '0' }).collect()
+}
+
+fn parse_flags(value: &str, names: &[&str; 3], param: &str) -> Result<[bool; 3], String> {
+    let value = value.trim().to_lowercase();
+    let mut flags = [false; 3];
+
+    if value.len() == 3 && value.chars().all(|c| c == '0' || c == '1') {
+        for (i, c) in value.chars().enumerate() {
+            flags[i] = c == '1';
+        }
+    } else {
+        for part in value.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            match names.iter().position(|name| *name == part) {
+                Some(i) => flags[i] = true,
+                None => return Err(format!(
+                    "Invalid {} '{}', expected a bit string like 100 or a list of {}",
+                    param, part, names.join(", ")
+                )),
+            }
+        }
+    }
+
+    if !flags.iter().any(|on| *on) {
+        return Err(format!("Invalid {} '{}', at least one must be enabled", param, value));
+    }
+    Ok(flags)
+}
+
+fn parse_pair(value: &str) -> Option<(u32, u32)> {
+    let (left, right) = value.trim().split_once('x')?;
+    let left: u32 = left.parse().ok()?;
+    let right: u32 = right.parse().ok()?;
+    if left == 0 || right == 0 {
+        return None;
+    }
+    Some((left, right))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(topic: &str) -> WHSearchParams {
+        WHSearchParams {
+            topic: topic.to_string(),
+            page: 1,
+            categories: None,
+            purity: None,
+            sorting: None,
+            order: None,
+            top_range: None,
+            atleast: None,
+            resolutions: None,
+            ratios: None,
+            colors: None,
+            seed: None,
+        }
+    }
+
+    #[test]
+    fn flags() {
+        assert_eq!("101".parse::<WHCategories>(), Ok(WHCategories { general: true, anime: false, people: true }));
+        assert_eq!(" General, anime ".parse::<WHCategories>(), Ok(WHCategories { general: true, anime: true, people: false }));
+        assert_eq!("sketchy,nsfw".parse::<WHPurity>().map(|p| p.as_param()), Ok("011".to_string()));
+        assert!("000".parse::<WHPurity>().is_err());
+        assert!("".parse::<WHCategories>().is_err());
+        assert!("general,cats".parse::<WHCategories>().unwrap_err().contains("cats"));
+        // Only a 3 character bit string is read as bits
+        assert!("1010".parse::<WHCategories>().is_err());
+    }
+
+    #[test]
+    fn values() {
+        assert_eq!("TopList".parse::<WHSorting>(), Ok(WHSorting::Toplist));
+        assert!("newest".parse::<WHSorting>().is_err());
+        assert_eq!("1M".parse::<WHTopRange>(), Ok(WHTopRange::OneMonth));
+        assert!("1m".parse::<WHTopRange>().is_err());
+        assert_eq!("1920x1080".parse::<WHResolution>(), Ok(WHResolution { width: 1920, height: 1080 }));
+        assert!("1920x0".parse::<WHResolution>().is_err());
+        assert!("1920".parse::<WHResolution>().is_err());
+        assert_eq!("Portrait".parse::<WHRatio>(), Ok(WHRatio::Portrait));
+        assert_eq!("16x9".parse::<WHRatio>(), Ok(WHRatio::Exact(16, 9)));
+    }
+
+    #[test]
+    fn to_query() {
+        let raw = WHSearchParams {
+            page: 2,
+            categories: Some("general,anime".to_string()),
+            purity: Some("100".to_string()),
+            order: Some("asc".to_string()),
+            top_range: Some("1w".to_string()),
+            atleast: Some("2560x1440".to_string()),
+            resolutions: Some("1920x1080, 3840x2160,".to_string()),
+            ratios: Some("16x9,landscape".to_string()),
+            colors: Some("#66CCFF".to_string()),
+            seed: Some(" abc123 ".to_string()),
+            ..params(" cats ")
+        };
+        let query = raw.to_query().unwrap();
+        assert_eq!(query.q, "cats");
+        assert_eq!(query.resolutions.len(), 2);
+        assert_eq!(query.to_query_pairs(), vec![
+            ("q", "cats".to_string()),
+            ("categories", "110".to_string()),
+            ("purity", "100".to_string()),
+            // topRange on its own picks the toplist
+            ("sorting", "toplist".to_string()),
+            ("order", "asc".to_string()),
+            ("topRange", "1w".to_string()),
+            ("atleast", "2560x1440".to_string()),
+            ("resolutions", "1920x1080,3840x2160".to_string()),
+            ("ratios", "16x9,landscape".to_string()),
+            ("colors", "66ccff".to_string()),
+            ("page", "2".to_string()),
+            ("seed", "abc123".to_string()),
+        ]);
+
+        assert_eq!(params("cats").to_query().unwrap().to_query_pairs(), vec![
+            ("q", "cats".to_string()),
+            ("page", "1".to_string()),
+        ]);
+        assert!(WHSearchParams { ratios: Some("16x9,wide".to_string()), ..params("cats") }.to_query().is_err());
+    }
+
+    #[test]
+    fn validate() {
+        assert!(WHSearchQuery::new("cats", 1).validate().is_ok());
+        assert!(WHSearchQuery::new("cats", 0).validate().is_err());
+
+        let mut query = WHSearchQuery::new("cats", 1);
+        query.top_range = Some(WHTopRange::OneDay);
+        query.sorting = Some(WHSorting::Toplist);
+        assert!(query.validate().is_ok());
+        query.sorting = Some(WHSorting::Views);
+        assert!(query.validate().unwrap_err().contains("topRange"));
+
+        let mut query = WHSearchQuery::new("cats", 1);
+        query.colors = Some("66ccff".to_string());
+        assert!(query.validate().is_ok());
+        query.colors = Some("66ccfg".to_string());
+        assert!(query.validate().is_err());
+        query.colors = Some("fff".to_string());
+        assert!(query.validate().is_err());
+
+        let mut query = WHSearchQuery::new("cats", 1);
+        query.seed = Some("abc12".to_string());
+        assert!(query.validate().is_err());
+        query.seed = Some("abc-12".to_string());
+        assert!(query.validate().is_err());
+    }
+}
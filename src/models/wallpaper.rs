@@ -0,0 +1,60 @@
+use serde::{ Deserialize, Serialize};
+
+// Provider neutral image model, every ImageSource converts its own responses into this
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Wallpaper {
+    // Name of the ImageSource it came from, e.g. "wallhaven"
+    pub source: String,
+    pub id: String,
+    // Page for the image on the provider's website
+    pub url: String,
+    pub thumbnail_url: String,
+    pub original_url: String,
+    pub width: u32,
+    pub height: u32,
+    // MIME type, e.g. "image/jpeg"
+    pub file_type: String,
+    #[serde(default)]
+    pub file_size: Option<u64>,
+    #[serde(default)]
+    pub author: Option<String>,
+    // Where the provider says the image originally came from
+    #[serde(default)]
+    pub source_url: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub colors: Vec<String>,
+    #[serde(default)]
+    pub purity: Option<String>,
+    #[serde(default)]
+    pub category: Option<String>,
+    #[serde(default)]
+    pub created_at: Option<String>,
+}
+
+impl Wallpaper {
+    // Files are stored as <source>-<id>.<ext>, e.g. wallhaven-1pvwjw.jpg
+    pub fn file_stem(&self) -> String {
+        format!("{}-{}", self.source, self.id)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SearchPage {
+    pub data: Vec<Wallpaper>,
+    pub meta: SearchMeta,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SearchMeta {
+    pub current_page: u32,
+    pub last_page: u32,
+    pub per_page: u32,
+    pub total: u64,
+    pub query: String,
+    // Wallhaven's random sorting seed, keeps pages consistent while browsing
+    #[serde(default)]
+    pub seed: Option<String>,
+}
@@ -0,0 +1,328 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use rand::Rng;
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+use tokio::task::JoinHandle;
+use crate::models::wallpaper::Wallpaper;
+use crate::sources::SearchQuery;
+use crate::{download, library, sources, utils, wallpaper};
+
+// Automatic wallpaper rotation
+// Runs as a background task inside the actix server and changes the wallpaper every N minutes
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RotationSource {
+    // Results of a saved search on any provider, pages are walked through as the queue runs out
+    Search { params: SearchQuery },
+    // A tag folder under the collections directory
+    Collection { tag: String },
+    // Everything already in the downloads folder
+    Downloads,
+    // Downloaded images from the library, optionally only favourites or those rated at least min_rating
+    Library {
+        #[serde(default)]
+        favorites_only: bool,
+        #[serde(default)]
+        min_rating: Option<u8>,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum RotationMode {
+    #[default]
+    Shuffle,
+    Sequential,
+    // Shuffle where favourites and higher rated images come up more often
+    Weighted,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RotationSettings {
+    pub source: RotationSource,
+    #[serde(default)]
+    pub mode: RotationMode,
+    pub interval_minutes: u64,
+    // Only rotate the wallpaper on this monitor, e.g. "HDMI-1"
+    #[serde(default)]
+    pub output: Option<String>,
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct RotationStatus {
+    pub running: bool,
+    pub settings: Option<RotationSettings>,
+    pub current_path: Option<String>,
+    pub last_changed_at: Option<u64>,
+    pub next_change_at: Option<u64>,
+    pub last_error: Option<String>,
+}
+
+// Shared between the actix workers through web::Data
+#[derive(Default)]
+pub struct Scheduler {
+    status: Arc<Mutex<RotationStatus>>,
+    handle: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Scheduler::default()
+    }
+
+    pub fn status(&self) -> RotationStatus {
+        self.status.lock().unwrap().clone()
+    }
+
+    // Replaces any running rotation, the first change happens straight away
+    pub fn start(&self, settings: RotationSettings) -> Result<RotationStatus, String> {
+        if settings.interval_minutes == 0 {
+            return Err("interval_minutes must be at least 1".to_string());
+        }
+        if let RotationSource::Search { params } = &settings.source {
+            sources::get_or_default(params.provider.as_deref()).map_err(|e| e.to_string())?;
+        }
+        if let RotationSource::Library { min_rating: Some(rating), .. } = &settings.source
+            && !(1..=5).contains(rating) {
+            return Err(format!("Invalid min_rating '{}', expected 1-5", rating));
+        }
+        if let RotationSource::Collection { tag } = &settings.source {
+            let tag = utils::sanitize_tag_name(tag);
+            if tag.is_empty() || !utils::get_collections_directory().join(&tag).is_dir() {
+                return Err(format!("Collection '{}' does not exist", tag));
+            }
+        }
+
+        // Held until the new task is stored, so two concurrent starts can't both leave a rotation running
+        let mut handle = self.handle.lock().unwrap();
+        if let Some(previous) = handle.take() {
+            previous.abort();
+        }
+
+        {
+            let mut status = self.status.lock().unwrap();
+            status.running = true;
+            status.settings = Some(settings.clone());
+            status.last_error = None;
+            status.next_change_at = Some(now());
+        }
+
+        *handle = Some(tokio::spawn(run(settings, Arc::clone(&self.status))));
+        drop(handle);
+
+        Ok(self.status())
+    }
+
+    pub fn stop(&self) -> RotationStatus {
+        if let Some(handle) = self.handle.lock().unwrap().take() {
+            handle.abort();
+        }
+        let mut status = self.status.lock().unwrap();
+        status.running = false;
+        status.next_change_at = None;
+        status.clone()
+    }
+}
+
+async fn run(settings: RotationSettings, status: Arc<Mutex<RotationStatus>>) {
+    let interval = Duration::from_secs(settings.interval_minutes * 60);
+    let mut ticker = tokio::time::interval(interval);
+    let mut playlist = Playlist::new(settings.source.clone(), settings.mode, settings.output.clone());
+
+    loop {
+        ticker.tick().await;
+
+        // Downloading and calling out to the desktop are blocking, keep them off the async workers
+        let result = tokio::task::spawn_blocking(move || {
+            let result = playlist.next_wallpaper().map_err(|e| e.to_string());
+            (playlist, result)
+        }).await;
+
+        let mut status = status.lock().unwrap();
+        status.next_change_at = Some(now() + interval.as_secs());
+        match result {
+            Ok((returned, result)) => {
+                playlist = returned;
+                match result {
+                    Ok(path) => {
+                        println!("Rotation: changed wallpaper to {}", path);
+                        status.current_path = Some(path);
+                        status.last_changed_at = Some(now());
+                        status.last_error = None;
+                    },
+                    Err(e) => {
+                        eprintln!("Rotation: failed to change wallpaper: {}", e);
+                        status.last_error = Some(e);
+                    }
+                }
+            },
+            Err(e) => {
+                eprintln!("Rotation task failed: {}", e);
+                status.running = false;
+                status.next_change_at = None;
+                status.last_error = Some(e.to_string());
+                return;
+            }
+        }
+    }
+}
+
+enum QueueItem {
+    Local(PathBuf),
+    Remote(Box<Wallpaper>),
+}
+
+// Queue of upcoming wallpapers, refilled from the source whenever it runs dry
+struct Playlist {
+    source: RotationSource,
+    mode: RotationMode,
+    queue: Vec<QueueItem>,
+    next_page: u16,
+    last_page: Option<u16>,
+    current: Option<String>,
+    output: Option<String>,
+}
+
+impl Playlist {
+    fn new(source: RotationSource, mode: RotationMode, output: Option<String>) -> Self {
+        let next_page = match &source {
+            RotationSource::Search { params } => params.page.max(1),
+            _ => 1,
+        };
+        Playlist { source, mode, queue: Vec::new(), next_page, last_page: None, current: None, output }
+    }
+
+    fn next_wallpaper(&mut self) -> Result<String, Box<dyn Error + Send + Sync>> {
+        if self.queue.is_empty() {
+            self.refill()?;
+        }
+        if self.queue.is_empty() {
+            return Err("No images found for the rotation source".into());
+        }
+
+        // Queue is kept reversed so pop() returns items in order
+        let path = match self.queue.pop().unwrap() {
+            QueueItem::Local(path) => path.to_string_lossy().to_string(),
+            QueueItem::Remote(image) => {
+                let source = sources::get_or_default(Some(&image.source))?;
+                let downloads = utils::get_downloads_directory();
+                download::image::original(source.as_ref(), &image, downloads.to_str().unwrap())?
+            }
+        };
+
+        match &self.output {
+            Some(output) => wallpaper::change_for_output(output, &path)?,
+            None => wallpaper::change(&path)?
+        };
+        self.current = Some(path.clone());
+        Ok(path)
+    }
+
+    fn refill(&mut self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let mut items: Vec<QueueItem> = match &self.source {
+            RotationSource::Downloads => list_images(&utils::get_downloads_directory())?
+                .into_iter()
+                .map(QueueItem::Local)
+                .collect(),
+            RotationSource::Collection { tag } => {
+                let tag_dir = utils::get_collections_directory().join(utils::sanitize_tag_name(tag));
+                list_images(&tag_dir)?
+                    .into_iter()
+                    .map(QueueItem::Local)
+                    .collect()
+            },
+            RotationSource::Library { favorites_only, min_rating } => {
+                let query = library::LibraryQuery {
+                    favorite: favorites_only.then_some(true),
+                    min_rating: *min_rating,
+                    ..Default::default()
+                };
+                let mut paths: Vec<PathBuf> = query.matching().into_iter()
+                    .map(|entry| PathBuf::from(entry.path))
+                    .filter(|path| path.is_file())
+                    .collect();
+                paths.sort();
+                paths.into_iter().map(QueueItem::Local).collect()
+            },
+            RotationSource::Search { params } => {
+                let source = sources::get_or_default(params.provider.as_deref())?;
+                let mut query = params.clone();
+                query.page = match (self.mode, self.last_page) {
+                    (RotationMode::Shuffle | RotationMode::Weighted, Some(last_page)) => rand::rng().random_range(1..=last_page),
+                    _ => self.next_page,
+                };
+
+                let response = source.search(&query)?;
+                let last_page = response.meta.last_page.clamp(1, u16::MAX as u32) as u16;
+                self.last_page = Some(last_page);
+                // Wrap round to the first page once the last one has been shown
+                self.next_page = if query.page >= last_page { 1 } else { query.page + 1 };
+
+                response.data.into_iter()
+                    .map(|image| QueueItem::Remote(Box::new(image)))
+                    .collect()
+            }
+        };
+
+        match self.mode {
+            RotationMode::Shuffle => items.shuffle(&mut rand::rng()),
+            RotationMode::Sequential => {}
+            RotationMode::Weighted => items = weighted_order(items),
+        }
+
+        // Avoid showing the same wallpaper twice in a row when a new cycle starts
+        if items.len() > 1 && self.current.is_some() {
+            let current = self.current.as_deref();
+            if let Some(QueueItem::Local(first)) = items.first()
+                && first.to_str() == current {
+                items.rotate_left(1);
+            }
+        }
+
+        items.reverse();
+        self.queue = items;
+        Ok(())
+    }
+}
+
+// Weights come from the library, images it doesn't know count as unrated
+fn weighted_order(items: Vec<QueueItem>) -> Vec<QueueItem> {
+    let entries = library::load().entries;
+    let by_path: HashMap<&str, f64> = entries.values().map(|entry| (entry.path.as_str(), entry.weight())).collect();
+    let unrated = 3.0;
+
+    let weighted = items.into_iter().map(|item| {
+        let weight = match &item {
+            QueueItem::Local(path) => by_path.get(path.to_string_lossy().as_ref()).copied(),
+            QueueItem::Remote(image) => entries.get(&library::key(&image.source, &image.id)).map(|entry| entry.weight()),
+        };
+        (item, weight.unwrap_or(unrated))
+    }).collect();
+    library::weighted_shuffle(weighted)
+}
+
+fn list_images(dir: &Path) -> Result<Vec<PathBuf>, Box<dyn Error + Send + Sync>> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut images: Vec<PathBuf> = fs::read_dir(dir)?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && utils::is_image(path))
+        .collect();
+    images.sort();
+    Ok(images)
+}
+
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
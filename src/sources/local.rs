@@ -0,0 +1,186 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use image::ImageFormat;
+use crate::models::wallpaper::{SearchMeta, SearchPage, Wallpaper};
+use crate::{config, utils};
+use super::{ImageSource, SearchQuery, SourceError};
+
+// Browses the folders listed under "local_folders" in config.json
+// Images are never copied, the original and thumbnail URLs are file:// paths on disk
+
+const PER_PAGE: usize = 24;
+// Same size as Wallhaven's small thumbnails
+const THUMBNAIL_WIDTH: u32 = 300;
+const THUMBNAIL_HEIGHT: u32 = 200;
+
+pub struct LocalSource;
+
+impl LocalSource {
+    // Every image under the configured folders, sorted by path
+    fn index() -> Result<Vec<PathBuf>, SourceError> {
+        let folders = config::load().local_folders;
+        if folders.is_empty() {
+            return Err(SourceError::InvalidQuery(
+                "No local folders configured, add them to \"local_folders\" in config.json".to_string()
+            ));
+        }
+
+        let mut images = Vec::new();
+        for folder in folders {
+            let folder = PathBuf::from(folder);
+            if !folder.is_dir() {
+                eprintln!("Local folder {} does not exist, skipping", folder.display());
+                continue;
+            }
+            walk(&folder, &mut images);
+        }
+        images.sort();
+        images.dedup();
+        Ok(images)
+    }
+
+    fn to_wallpaper(path: &Path) -> Result<Wallpaper, SourceError> {
+        let format = ImageFormat::from_path(path)
+            .map_err(|e| SourceError::Request(Box::new(e)))?;
+        let (width, height) = image::image_dimensions(path)
+            .map_err(|e| SourceError::Request(Box::new(e)))?;
+        let metadata = fs::metadata(path)?;
+        let url = file_url(path);
+
+        Ok(Wallpaper {
+            source: "local".to_string(),
+            id: path_id(path),
+            url: url.clone(),
+            thumbnail_url: url.clone(),
+            original_url: url,
+            width,
+            height,
+            file_type: format.to_mime_type().to_string(),
+            file_size: Some(metadata.len()),
+            author: None,
+            source_url: None,
+            tags: Vec::new(),
+            colors: Vec::new(),
+            purity: None,
+            category: None,
+            created_at: None,
+        })
+    }
+}
+
+impl ImageSource for LocalSource {
+    fn name(&self) -> &'static str {
+        "local"
+    }
+
+    // The topic filters on the file name, an empty topic lists everything
+    fn search(&self, query: &SearchQuery) -> Result<SearchPage, SourceError> {
+        let topic = query.topic.trim().to_lowercase();
+        let images: Vec<PathBuf> = LocalSource::index()?
+            .into_iter()
+            .filter(|path| topic.is_empty() || path.file_name()
+                .map(|name| name.to_string_lossy().to_lowercase().contains(&topic))
+                .unwrap_or(false))
+            .collect();
+
+        let total = images.len();
+        let last_page = total.div_ceil(PER_PAGE).max(1);
+        let start = (query.page as usize - 1) * PER_PAGE;
+
+        let thumbnails_folder = utils::get_thumbnails_directory();
+        utils::ensure_dir(&thumbnails_folder)?;
+
+        let mut data = Vec::new();
+        for path in images.iter().skip(start).take(PER_PAGE) {
+            match LocalSource::to_wallpaper(path) {
+                Ok(mut wallpaper) => {
+                    match create_thumbnail(path, &thumbnails_folder.join(format!("{}.jpg", wallpaper.file_stem()))) {
+                        Ok(thumbnail) => wallpaper.thumbnail_url = file_url(&thumbnail),
+                        Err(e) => eprintln!("Failed to create thumbnail for {}: {}", path.display(), e)
+                    }
+                    data.push(wallpaper);
+                },
+                // Unreadable or corrupt files are left out rather than failing the whole page
+                Err(e) => eprintln!("Skipping {}: {}", path.display(), e)
+            }
+        }
+
+        Ok(SearchPage {
+            data,
+            meta: SearchMeta {
+                current_page: query.page as u32,
+                last_page: last_page as u32,
+                per_page: PER_PAGE as u32,
+                total: total as u64,
+                query: query.topic.clone(),
+                seed: None,
+            },
+        })
+    }
+
+    fn get_by_id(&self, id: &str) -> Result<Wallpaper, SourceError> {
+        let path = LocalSource::index()?
+            .into_iter()
+            .find(|path| path_id(path) == id)
+            .ok_or_else(|| SourceError::NotFound(format!("No local image with id '{}'", id)))?;
+        LocalSource::to_wallpaper(&path)
+    }
+}
+
+fn walk(dir: &Path, images: &mut Vec<PathBuf>) {
+    let read_dir = match fs::read_dir(dir) {
+        Ok(read_dir) => read_dir,
+        Err(e) => {
+            eprintln!("Failed to read {}: {}", dir.display(), e);
+            return;
+        }
+    };
+
+    for entry in read_dir.flatten() {
+        let path = entry.path();
+        let hidden = entry.file_name().to_string_lossy().starts_with('.');
+        match entry.file_type() {
+            // Symlinked folders are not followed to avoid loops
+            Ok(file_type) if file_type.is_dir() => {
+                if !hidden {
+                    walk(&path, images);
+                }
+            },
+            Ok(_) => {
+                if !hidden && path.is_file() && utils::is_image(&path) {
+                    images.push(path);
+                }
+            },
+            Err(_) => {}
+        }
+    }
+}
+
+// Stable id for a file, FNV-1a hash of its path
+fn path_id(path: &Path) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in path.to_string_lossy().as_bytes() {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    format!("{:016x}", hash)
+}
+
+pub fn file_url(path: &Path) -> String {
+    format!("file://{}", path.display())
+}
+
+// Thumbnails are regenerated when the original is newer
+fn create_thumbnail(path: &Path, thumbnail: &Path) -> Result<PathBuf, Box<dyn std::error::Error + Send + Sync>> {
+    if let (Ok(original), Ok(existing)) = (fs::metadata(path), fs::metadata(thumbnail))
+        && let (Ok(original), Ok(existing)) = (original.modified(), existing.modified())
+        && existing >= original {
+        return Ok(thumbnail.to_path_buf());
+    }
+
+    let image = image::open(path)?;
+    image.thumbnail(THUMBNAIL_WIDTH, THUMBNAIL_HEIGHT)
+        .to_rgb8()
+        .save_with_format(thumbnail, ImageFormat::Jpeg)?;
+    Ok(thumbnail.to_path_buf())
+}
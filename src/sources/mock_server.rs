@@ -0,0 +1,57 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+// A one-thread HTTP server for provider tests, it answers each request with the next canned response
+// and keeps the request line and headers so the tests can check what was sent
+
+pub struct MockServer {
+    pub url: String,
+    requests: Arc<Mutex<Vec<String>>>,
+}
+
+impl MockServer {
+    pub fn start(responses: Vec<(u16, String)>) -> MockServer {
+        MockServer::start_raw(responses.into_iter()
+            .map(|(status, body)| format!(
+                "HTTP/1.1 {} Mock\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            ).into_bytes())
+            .collect())
+    }
+
+    // Sends each response exactly as given, status line and headers included, e.g. to cut a body short
+    pub fn start_raw(responses: Vec<Vec<u8>>) -> MockServer {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let recorded = requests.clone();
+        thread::spawn(move || {
+            for response in responses {
+                let Ok((stream, _)) = listener.accept() else {
+                    return;
+                };
+                let mut reader = BufReader::new(stream);
+                let mut head = String::new();
+                loop {
+                    let mut line = String::new();
+                    if reader.read_line(&mut line).unwrap_or(0) == 0 || line == "\r\n" {
+                        break;
+                    }
+                    head.push_str(&line);
+                }
+                recorded.lock().unwrap().push(head);
+                let _ = reader.into_inner().write_all(&response);
+            }
+        });
+        MockServer { url, requests }
+    }
+
+    // Request line and headers of every request so far, e.g. "GET /search?query=cats HTTP/1.1\r\n..."
+    pub fn requests(&self) -> Vec<String> {
+        self.requests.lock().unwrap().clone()
+    }
+}
@@ -0,0 +1,214 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use serde::{Deserialize, Serialize};
+use crate::library;
+use crate::models::wallpaper::{SearchPage, Wallpaper};
+
+pub mod local;
+#[cfg(test)]
+pub(crate) mod mock_server;
+pub mod pexels;
+pub mod reddit;
+pub mod unsplash;
+pub mod wallhaven;
+
+// Image providers
+// Each provider implements ImageSource and converts its own API models into models::wallpaper
+
+pub const DEFAULT_SOURCE: &str = "wallhaven";
+// Providers whose topic is free text, the others filter on file names (local) or subreddits (reddit)
+const TEXT_SEARCH_SOURCES: [&str; 3] = ["wallhaven", "unsplash", "pexels"];
+// More tags than this rarely match anything
+const SIMILAR_TAGS: usize = 3;
+
+pub trait ImageSource: Send + Sync {
+    fn name(&self) -> &'static str;
+    fn search(&self, query: &SearchQuery) -> Result<SearchPage, SourceError>;
+    fn get_by_id(&self, id: &str) -> Result<Wallpaper, SourceError>;
+
+    fn thumbnail_url(&self, wallpaper: &Wallpaper) -> String {
+        wallpaper.thumbnail_url.clone()
+    }
+
+    fn original_url(&self, wallpaper: &Wallpaper) -> String {
+        wallpaper.original_url.clone()
+    }
+}
+
+pub fn get(name: &str) -> Option<Box<dyn ImageSource>> {
+    match name.trim().to_lowercase().as_str() {
+        "wallhaven" => Some(Box::new(wallhaven::WallhavenSource)),
+        "local" => Some(Box::new(local::LocalSource)),
+        "unsplash" => Some(Box::new(unsplash::UnsplashSource)),
+        "pexels" => Some(Box::new(pexels::PexelsSource)),
+        "reddit" => Some(Box::new(reddit::RedditSource)),
+        _ => None
+    }
+}
+
+pub const SOURCE_NAMES: [&str; 5] = ["wallhaven", "local", "unsplash", "pexels", "reddit"];
+
+// Looks the provider up by name, defaulting to Wallhaven when none is given
+pub fn get_or_default(name: Option<&str>) -> Result<Box<dyn ImageSource>, SourceError> {
+    let name = name.map(str::trim).filter(|n| !n.is_empty()).unwrap_or(DEFAULT_SOURCE);
+    get(name).ok_or_else(|| SourceError::InvalidQuery(format!(
+        "Unknown provider '{}', expected one of {}",
+        name,
+        SOURCE_NAMES.join(", ")
+    )))
+}
+
+// Provider neutral search request, anything other than the topic and page is passed
+// through as options and interpreted by the provider (e.g. Wallhaven's sorting or purity)
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SearchQuery {
+    #[serde(default)]
+    pub provider: Option<String>,
+    #[serde(default)]
+    pub topic: String,
+    #[serde(default = "default_page")]
+    pub page: u16,
+    #[serde(default, flatten)]
+    pub options: HashMap<String, String>,
+}
+
+fn default_page() -> u16 { 1 }
+
+impl SearchQuery {
+    // Builds a query from raw query string parameters, e.g. GET /search?provider=&topic=&page=&sorting=
+    pub fn from_params(mut params: HashMap<String, String>) -> Result<Self, String> {
+        let provider = params.remove("provider").filter(|p| !p.trim().is_empty());
+        let topic = params.remove("topic").unwrap_or_default();
+        let page = match params.remove("page") {
+            Some(page) => page.trim().parse::<u16>()
+                .map_err(|_| format!("Invalid page '{}'", page))?,
+            None => default_page(),
+        };
+        if page == 0 {
+            return Err("Invalid page '0', pages start at 1".to_string());
+        }
+        Ok(SearchQuery { provider, topic, page, options: params })
+    }
+
+    pub fn option(&self, key: &str) -> Option<&str> {
+        self.options.get(key).map(String::as_str).filter(|value| !value.trim().is_empty())
+    }
+
+    // Like option() but rejects anything outside the allowed values
+    pub fn option_one_of(&self, key: &str, allowed: &[&'static str]) -> Result<Option<&'static str>, SourceError> {
+        let Some(value) = self.option(key) else {
+            return Ok(None);
+        };
+        let value = value.trim().to_lowercase();
+        allowed.iter()
+            .find(|allowed| **allowed == value)
+            .map(|allowed| Some(*allowed))
+            .ok_or_else(|| SourceError::InvalidQuery(format!(
+                "Invalid {} '{}', expected one of {}", key, value, allowed.join(", ")
+            )))
+    }
+}
+
+// How "more like this" looks for related images
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SimilarBy {
+    // Wallhaven's like:<id> search, only for Wallhaven images
+    Like,
+    // A search for the image's first few tags
+    Tags,
+}
+
+impl std::str::FromStr for SimilarBy {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_lowercase().as_str() {
+            "like" => Ok(SimilarBy::Like),
+            "tags" => Ok(SimilarBy::Tags),
+            _ => Err(format!("Invalid by '{}', expected like or tags", value)),
+        }
+    }
+}
+
+// Fills in the provider and topic of the query to find images like the given one, defaulting to like:<id>
+// for Wallhaven images searched on Wallhaven and to a tag search in the same provider (Wallhaven if it has no text search) otherwise
+pub fn similar(source_name: &str, id: &str, by: Option<SimilarBy>, mut query: SearchQuery) -> Result<SearchQuery, SourceError> {
+    let source = get_or_default(Some(source_name))?;
+    let like_possible = source.name() == "wallhaven" && query.provider.as_deref().is_none_or(|provider| provider == "wallhaven");
+    let by = by.unwrap_or(if like_possible { SimilarBy::Like } else { SimilarBy::Tags });
+
+    match by {
+        SimilarBy::Like => {
+            if source.name() != "wallhaven" {
+                return Err(SourceError::InvalidQuery("like only works for Wallhaven images, use by=tags".to_string()));
+            }
+            if query.provider.as_deref().is_some_and(|provider| provider != "wallhaven") {
+                return Err(SourceError::InvalidQuery("like searches can only run on Wallhaven".to_string()));
+            }
+            query.provider = Some(source.name().to_string());
+            query.topic = wallhaven::WallhavenSource::like_topic(id);
+        },
+        SimilarBy::Tags => {
+            // Search results don't include tags, so ask the provider when the library has none
+            let tags = match library::get(source.name(), id) {
+                Some(entry) if !entry.wallpaper.tags.is_empty() => entry.wallpaper.tags,
+                _ => source.get_by_id(id)?.tags,
+            };
+            if tags.is_empty() {
+                return Err(SourceError::NotFound(format!("{} has no tags to search for", library::key(source.name(), id))));
+            }
+            if query.provider.is_none() {
+                let provider = if TEXT_SEARCH_SOURCES.contains(&source.name()) { source.name() } else { DEFAULT_SOURCE };
+                query.provider = Some(provider.to_string());
+            }
+            query.topic = tags.into_iter().take(SIMILAR_TAGS).collect::<Vec<_>>().join(" ");
+        },
+    }
+    Ok(query)
+}
+
+#[derive(Debug)]
+pub enum SourceError {
+    // Something wrong with what was asked for, e.g. an invalid filter value
+    InvalidQuery(String),
+    NotFound(String),
+    // The provider couldn't be reached or returned something unexpected
+    Request(Box<dyn Error + Send + Sync>),
+}
+
+impl fmt::Display for SourceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SourceError::InvalidQuery(message) => write!(f, "{}", message),
+            SourceError::NotFound(message) => write!(f, "{}", message),
+            SourceError::Request(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for SourceError {}
+
+impl From<Box<dyn Error + Send + Sync>> for SourceError {
+    fn from(e: Box<dyn Error + Send + Sync>) -> Self {
+        SourceError::Request(e)
+    }
+}
+
+impl From<ureq::Error> for SourceError {
+    fn from(e: ureq::Error) -> Self {
+        match e {
+            ureq::Error::StatusCode(404) => SourceError::NotFound("Image not found".to_string()),
+            ureq::Error::StatusCode(code @ (401 | 403)) => SourceError::Request(
+                format!("The provider rejected the API key (HTTP {})", code).into()
+            ),
+            e => SourceError::Request(Box::new(e)),
+        }
+    }
+}
+
+impl From<std::io::Error> for SourceError {
+    fn from(e: std::io::Error) -> Self {
+        SourceError::Request(Box::new(e))
+    }
+}
@@ -0,0 +1,197 @@
+use crate::config::{self, AppConfig};
+use crate::models::pexels::{PexelsPhoto, PexelsSearchResponse};
+use crate::models::wallpaper::{SearchPage, Wallpaper};
+use crate::utils;
+use super::{ImageSource, SearchQuery, SourceError};
+
+// https://www.pexels.com/api/documentation/, needs an API key from a Pexels account
+
+const PER_PAGE: u32 = 24;
+
+pub struct PexelsSource;
+
+// The config is passed in so tests can point the requests at a mock server
+impl PexelsSource {
+    fn request(config: &AppConfig, path: &str, params: &[(&str, String)]) -> Result<ureq::RequestBuilder<ureq::typestate::WithoutBody>, SourceError> {
+        let api_key = config.pexels_api_key.as_deref()
+            .filter(|key| !key.trim().is_empty())
+            .ok_or_else(|| SourceError::InvalidQuery(
+                "No Pexels API key configured, add \"pexels_api_key\" to config.json".to_string()
+            ))?;
+        let base = config.pexels_api_url.as_deref().unwrap_or(utils::PEXELS_API);
+
+        let url = utils::api_url(base, path, params);
+        println!("Pexels query: {}", url);
+        Ok(utils::api_request(&url).header("Authorization", api_key.trim()))
+    }
+
+    // Optional filters: orientation, size, color
+    // Without a topic this lists Pexels' curated photos
+    fn search_with(config: &AppConfig, query: &SearchQuery) -> Result<SearchPage, SourceError> {
+        let mut params = vec![
+            ("page", query.page.to_string()),
+            ("per_page", PER_PAGE.to_string()),
+        ];
+
+        let path = if query.topic.trim().is_empty() {
+            "curated"
+        } else {
+            params.push(("query", query.topic.trim().to_string()));
+            if let Some(orientation) = query.option_one_of("orientation", &["landscape", "portrait", "square"])? {
+                params.push(("orientation", orientation.to_string()));
+            }
+            if let Some(size) = query.option_one_of("size", &["large", "medium", "small"])? {
+                params.push(("size", size.to_string()));
+            }
+            // Either a colour name or a hex code, Pexels validates it
+            if let Some(color) = query.option("color") {
+                params.push(("color", color.trim_start_matches('#').to_string()));
+            }
+            "search"
+        };
+
+        let response = PexelsSource::request(config, path, &params)?
+            .call()?
+            .body_mut()
+            .read_json::<PexelsSearchResponse>()?;
+        Ok(response.into_page(&query.topic))
+    }
+
+    fn get_by_id_with(config: &AppConfig, id: &str) -> Result<Wallpaper, SourceError> {
+        // Pexels ids are numeric, anything else would just 404
+        if id.is_empty() || !id.chars().all(|c| c.is_ascii_digit()) {
+            return Err(SourceError::NotFound(format!("Invalid Pexels photo id '{}'", id)));
+        }
+        let photo = PexelsSource::request(config, &format!("photos/{}", id), &[])?
+            .call()?
+            .body_mut()
+            .read_json::<PexelsPhoto>()?;
+        Ok(photo.into())
+    }
+}
+
+impl ImageSource for PexelsSource {
+    fn name(&self) -> &'static str {
+        "pexels"
+    }
+
+    fn search(&self, query: &SearchQuery) -> Result<SearchPage, SourceError> {
+        PexelsSource::search_with(&config::load(), query)
+    }
+
+    fn get_by_id(&self, id: &str) -> Result<Wallpaper, SourceError> {
+        PexelsSource::get_by_id_with(&config::load(), id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use super::*;
+    use crate::sources::mock_server::MockServer;
+
+    const PHOTO: &str = r##"{
+        "id": 2014422,
+        "width": 3024,
+        "height": 3024,
+        "url": "https://www.pexels.com/photo/brown-rocks-during-golden-hour-2014422/",
+        "photographer": "Joey Farina",
+        "photographer_url": "https://www.pexels.com/@joey",
+        "photographer_id": 680589,
+        "avg_color": "#978E82",
+        "src": {
+            "original": "https://images.pexels.com/photos/2014422/pexels-photo-2014422.jpeg",
+            "large2x": "https://images.pexels.com/photos/2014422/pexels-photo-2014422.jpeg?auto=compress&cs=tinysrgb&dpr=2&h=650&w=940",
+            "large": "https://images.pexels.com/photos/2014422/pexels-photo-2014422.jpeg?auto=compress&cs=tinysrgb&h=650&w=940",
+            "medium": "https://images.pexels.com/photos/2014422/pexels-photo-2014422.jpeg?auto=compress&cs=tinysrgb&h=350",
+            "small": "https://images.pexels.com/photos/2014422/pexels-photo-2014422.jpeg?auto=compress&cs=tinysrgb&h=130",
+            "portrait": "https://images.pexels.com/photos/2014422/pexels-photo-2014422.jpeg?auto=compress&cs=tinysrgb&fit=crop&h=1200&w=800",
+            "landscape": "https://images.pexels.com/photos/2014422/pexels-photo-2014422.jpeg?auto=compress&cs=tinysrgb&fit=crop&h=627&w=1200",
+            "tiny": "https://images.pexels.com/photos/2014422/pexels-photo-2014422.jpeg?auto=compress&cs=tinysrgb&dpr=1&fit=crop&h=200&w=280"
+        },
+        "liked": false,
+        "alt": "Brown Rocks During Golden Hour"
+    }"##;
+
+    fn config(url: &str) -> AppConfig {
+        AppConfig {
+            pexels_api_key: Some("563492ad6f917".to_string()),
+            pexels_api_url: Some(url.to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn query(params: &[(&str, &str)]) -> SearchQuery {
+        let params: HashMap<String, String> = params.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        SearchQuery::from_params(params).unwrap()
+    }
+
+    #[test]
+    fn search_parses_results_and_pages() {
+        let body = format!(
+            r#"{{ "total_results": 10000, "page": 2, "per_page": 24, "photos": [{}], "next_page": "https://api.pexels.com/v1/search/?page=3&per_page=24&query=nature" }}"#,
+            PHOTO
+        );
+        let server = MockServer::start(vec![(200, body)]);
+        let page = PexelsSource::search_with(&config(&server.url), &query(&[("topic", "nature"), ("page", "2"), ("color", "#ff0000")])).unwrap();
+
+        assert_eq!(page.meta.current_page, 2);
+        assert_eq!(page.meta.last_page, 417);
+        assert_eq!(page.meta.total, 10000);
+        assert_eq!(page.data.len(), 1);
+        let image = &page.data[0];
+        assert_eq!(image.source, "pexels");
+        assert_eq!(image.id, "2014422");
+        assert_eq!(image.file_type, "image/jpeg");
+        assert!(image.thumbnail_url.ends_with("&h=350"));
+        assert_eq!(image.original_url, "https://images.pexels.com/photos/2014422/pexels-photo-2014422.jpeg");
+        assert_eq!(image.author.as_deref(), Some("Joey Farina"));
+        assert_eq!(image.source_url.as_deref(), Some("https://www.pexels.com/@joey"));
+
+        let requests = server.requests();
+        assert!(requests[0].starts_with("GET /search?page=2&per_page=24&query=nature&color=ff0000 HTTP/1.1\r\n"));
+        let headers = requests[0].to_lowercase();
+        assert!(headers.contains("authorization: 563492ad6f917\r\n"));
+        assert!(headers.contains("user-agent: wallpaper_changer/"));
+    }
+
+    #[test]
+    fn curated_without_topic() {
+        let body = r#"{ "total_results": 48, "page": 1, "per_page": 24, "photos": [] }"#.to_string();
+        let server = MockServer::start(vec![(200, body)]);
+        let page = PexelsSource::search_with(&config(&server.url), &query(&[])).unwrap();
+        assert!(page.data.is_empty());
+        assert_eq!(page.meta.last_page, 2);
+        assert!(server.requests()[0].starts_with("GET /curated?page=1&per_page=24 HTTP/1.1\r\n"));
+    }
+
+    #[test]
+    fn get_by_id() {
+        let server = MockServer::start(vec![(200, PHOTO.to_string()), (404, r#"{ "status": 404 }"#.to_string())]);
+        let image = PexelsSource::get_by_id_with(&config(&server.url), "2014422").unwrap();
+        assert_eq!((image.width, image.height), (3024, 3024));
+        assert_eq!(image.colors, vec!["#978E82"]);
+
+        let error = PexelsSource::get_by_id_with(&config(&server.url), "1").unwrap_err();
+        assert!(matches!(error, SourceError::NotFound(_)));
+        let requests = server.requests();
+        assert!(requests[0].starts_with("GET /photos/2014422 HTTP/1.1\r\n"));
+        assert!(requests[1].starts_with("GET /photos/1 HTTP/1.1\r\n"));
+
+        // Not numeric, refused without a request
+        let error = PexelsSource::get_by_id_with(&config(&server.url), "../curated").unwrap_err();
+        assert!(matches!(error, SourceError::NotFound(_)));
+    }
+
+    #[test]
+    fn missing_key() {
+        let mut config = config("http://127.0.0.1:9");
+        config.pexels_api_key = None;
+        let error = PexelsSource::search_with(&config, &query(&[("topic", "cats")])).unwrap_err();
+        assert!(matches!(&error, SourceError::InvalidQuery(message) if message.contains("pexels_api_key")));
+
+        config.pexels_api_key = Some(String::new());
+        let error = PexelsSource::get_by_id_with(&config, "2014422").unwrap_err();
+        assert!(matches!(&error, SourceError::InvalidQuery(message) if message.contains("pexels_api_key")));
+    }
+}
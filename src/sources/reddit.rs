@@ -0,0 +1,160 @@
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex};
+use crate::config;
+use crate::models::reddit::RedditListing;
+use crate::models::wallpaper::{SearchMeta, SearchPage, Wallpaper};
+use crate::utils;
+use super::{ImageSource, SearchQuery, SourceError};
+
+// Subreddit listings through Reddit's public JSON endpoints, no account needed
+// The topic is the subreddit, e.g. "wallpapers" or "EarthPorn+SkyPorn" for several at once
+
+const DEFAULT_SUBREDDIT: &str = "wallpapers";
+const PER_PAGE: u32 = 25;
+
+// Reddit pages with "after" tokens rather than numbers, remember the token that starts each page
+// so /search?page=N keeps working, keyed by "<subreddits>/<sort>/<time>/<page>"
+static AFTER_TOKENS: LazyLock<Mutex<HashMap<String, String>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+pub struct RedditSource;
+
+impl RedditSource {
+    fn base_url() -> String {
+        config::load().reddit_api_url.unwrap_or_else(|| utils::REDDIT_API.to_string())
+    }
+
+    fn subreddits(topic: &str) -> Result<String, SourceError> {
+        let topic = topic.trim().trim_start_matches("/r/").trim_start_matches("r/").trim_matches('/');
+        if topic.is_empty() {
+            return Ok(DEFAULT_SUBREDDIT.to_string());
+        }
+        if !topic.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '+') {
+            return Err(SourceError::InvalidQuery(format!("Invalid subreddit '{}'", topic)));
+        }
+        Ok(topic.to_string())
+    }
+
+    fn listing(path: &str, params: &[(&str, String)]) -> Result<RedditListing, SourceError> {
+        let url = utils::api_url(&RedditSource::base_url(), path, params);
+        println!("Reddit query: {}", url);
+        Ok(utils::api_request(&url)
+            .call()?
+            .body_mut()
+            .read_json::<RedditListing>()?)
+    }
+}
+
+impl ImageSource for RedditSource {
+    fn name(&self) -> &'static str {
+        "reddit"
+    }
+
+    // Optional filters: sort (hot, top, new), time (hour, day, week, month, year, all) for top,
+    // nsfw=true to include NSFW posts
+    fn search(&self, query: &SearchQuery) -> Result<SearchPage, SourceError> {
+        let subreddits = RedditSource::subreddits(&query.topic)?;
+        let sort = query.option_one_of("sort", &["hot", "top", "new"])?.unwrap_or("hot");
+        let time = query.option_one_of("time", &["hour", "day", "week", "month", "year", "all"])?;
+        if time.is_some() && sort != "top" {
+            return Err(SourceError::InvalidQuery("time only applies to sort=top".to_string()));
+        }
+        let include_nsfw = query.option_one_of("nsfw", &["true", "false"])? == Some("true");
+
+        let key = |page: u16| format!("{}/{}/{}/{}", subreddits.to_lowercase(), sort, time.unwrap_or(""), page);
+        let path = format!("r/{}/{}.json", subreddits, sort);
+
+        // Walk forward from the closest page we know the token for
+        let mut page = query.page;
+        let mut after = None;
+        {
+            let tokens = AFTER_TOKENS.lock().unwrap();
+            while page > 1 {
+                if let Some(token) = tokens.get(&key(page)) {
+                    after = Some(token.clone());
+                    break;
+                }
+                page -= 1;
+            }
+        }
+
+        loop {
+            let mut params = vec![
+                ("limit", PER_PAGE.to_string()),
+                // Stops Reddit HTML-escaping the preview URLs
+                ("raw_json", "1".to_string()),
+            ];
+            if let Some(time) = time {
+                params.push(("t", time.to_string()));
+            }
+            if let Some(after) = &after {
+                params.push(("after", after.clone()));
+            }
+
+            let listing = RedditSource::listing(&path, &params)?;
+            if let Some(next) = &listing.data.after {
+                AFTER_TOKENS.lock().unwrap().insert(key(page + 1), next.clone());
+            }
+
+            if page < query.page {
+                match listing.data.after {
+                    Some(next) => {
+                        after = Some(next);
+                        page += 1;
+                        continue;
+                    },
+                    // Asked for a page past the end
+                    None => return Err(SourceError::NotFound(format!("r/{} has no page {}", subreddits, query.page)))
+                }
+            }
+
+            let data: Vec<Wallpaper> = listing.data.children.iter()
+                .filter(|child| include_nsfw || !child.data.over_18)
+                .filter_map(|child| child.data.to_wallpaper())
+                .collect();
+            let has_more = listing.data.after.is_some();
+
+            return Ok(SearchPage {
+                meta: SearchMeta {
+                    current_page: query.page as u32,
+                    // The real number of pages isn't known, there is always one more until Reddit runs out
+                    last_page: query.page as u32 + has_more as u32,
+                    per_page: PER_PAGE,
+                    total: data.len() as u64,
+                    query: subreddits,
+                    seed: None,
+                },
+                data,
+            });
+        }
+    }
+
+    fn get_by_id(&self, id: &str) -> Result<Wallpaper, SourceError> {
+        if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err(SourceError::InvalidQuery(format!("Invalid Reddit post id '{}'", id)));
+        }
+        let listing = RedditSource::listing(&format!("by_id/t3_{}.json", id), &[("raw_json", "1".to_string())])?;
+        let post = listing.data.children.into_iter().next()
+            .ok_or_else(|| SourceError::NotFound(format!("Reddit post '{}' not found", id)))?;
+        post.data.to_wallpaper()
+            .ok_or_else(|| SourceError::NotFound(format!("Reddit post '{}' doesn't link to an image", id)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn subreddits() {
+        assert_eq!(RedditSource::subreddits(" /r/EarthPorn/ ").unwrap(), "EarthPorn");
+        assert_eq!(RedditSource::subreddits("r/EarthPorn+SkyPorn").unwrap(), "EarthPorn+SkyPorn");
+        assert_eq!(RedditSource::subreddits("").unwrap(), DEFAULT_SUBREDDIT);
+        assert!(matches!(RedditSource::subreddits("wall papers"), Err(SourceError::InvalidQuery(_))));
+    }
+
+    #[test]
+    fn invalid_id_is_rejected_before_any_request() {
+        assert!(matches!(RedditSource.get_by_id("1abc/../x"), Err(SourceError::InvalidQuery(_))));
+        assert!(matches!(RedditSource.get_by_id(""), Err(SourceError::InvalidQuery(_))));
+    }
+}
@@ -0,0 +1,206 @@
+use crate::config::{self, AppConfig};
+use crate::models::unsplash::{UnsplashPhoto, UnsplashSearchResponse};
+use crate::models::wallpaper::{SearchPage, Wallpaper};
+use crate::utils;
+use super::{ImageSource, SearchQuery, SourceError};
+
+// https://unsplash.com/documentation, needs an access key from an Unsplash developer app
+
+const PER_PAGE: u32 = 24;
+
+pub struct UnsplashSource;
+
+// The config is passed in so tests can point the requests at a mock server
+impl UnsplashSource {
+    fn request(config: &AppConfig, path: &str, params: &[(&str, String)]) -> Result<ureq::RequestBuilder<ureq::typestate::WithoutBody>, SourceError> {
+        let api_key = config.unsplash_api_key.as_deref()
+            .filter(|key| !key.trim().is_empty())
+            .ok_or_else(|| SourceError::InvalidQuery(
+                "No Unsplash access key configured, add \"unsplash_api_key\" to config.json".to_string()
+            ))?;
+        let base = config.unsplash_api_url.as_deref().unwrap_or(utils::UNSPLASH_API);
+
+        let url = utils::api_url(base, path, params);
+        println!("Unsplash query: {}", url);
+        Ok(utils::api_request(&url)
+            .header("Authorization", format!("Client-ID {}", api_key.trim()))
+            .header("Accept-Version", "v1"))
+    }
+
+    // Optional filters: orientation, color, order_by, content_filter
+    fn search_with(config: &AppConfig, query: &SearchQuery) -> Result<SearchPage, SourceError> {
+        if query.topic.trim().is_empty() {
+            return Err(SourceError::InvalidQuery("Unsplash needs a topic to search for".to_string()));
+        }
+
+        let mut params = vec![
+            ("query", query.topic.trim().to_string()),
+            ("page", query.page.to_string()),
+            ("per_page", PER_PAGE.to_string()),
+        ];
+        if let Some(orientation) = query.option_one_of("orientation", &["landscape", "portrait", "squarish"])? {
+            params.push(("orientation", orientation.to_string()));
+        }
+        if let Some(color) = query.option_one_of("color", &[
+            "black_and_white", "black", "white", "yellow", "orange", "red", "purple", "magenta", "green", "teal", "blue"
+        ])? {
+            params.push(("color", color.to_string()));
+        }
+        if let Some(order_by) = query.option_one_of("order_by", &["relevant", "latest"])? {
+            params.push(("order_by", order_by.to_string()));
+        }
+        if let Some(content_filter) = query.option_one_of("content_filter", &["low", "high"])? {
+            params.push(("content_filter", content_filter.to_string()));
+        }
+
+        let response = UnsplashSource::request(config, "search/photos", &params)?
+            .call()?
+            .body_mut()
+            .read_json::<UnsplashSearchResponse>()?;
+        Ok(response.into_page(&query.topic, query.page, PER_PAGE))
+    }
+
+    fn get_by_id_with(config: &AppConfig, id: &str) -> Result<Wallpaper, SourceError> {
+        let photo = UnsplashSource::request(config, &format!("photos/{}", id), &[])?
+            .call()?
+            .body_mut()
+            .read_json::<UnsplashPhoto>()?;
+        Ok(photo.into())
+    }
+}
+
+impl ImageSource for UnsplashSource {
+    fn name(&self) -> &'static str {
+        "unsplash"
+    }
+
+    fn search(&self, query: &SearchQuery) -> Result<SearchPage, SourceError> {
+        UnsplashSource::search_with(&config::load(), query)
+    }
+
+    fn get_by_id(&self, id: &str) -> Result<Wallpaper, SourceError> {
+        UnsplashSource::get_by_id_with(&config::load(), id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use super::*;
+    use crate::sources::mock_server::MockServer;
+
+    const PHOTO: &str = r##"{
+        "id": "Dwu85P9SOIk",
+        "created_at": "2016-05-03T11:00:28-04:00",
+        "width": 2448,
+        "height": 3264,
+        "color": "#6E633A",
+        "description": "A man drinking a coffee.",
+        "alt_description": null,
+        "urls": {
+            "raw": "https://images.unsplash.com/photo-1417325384643-aac51acc9e5d",
+            "full": "https://images.unsplash.com/photo-1417325384643-aac51acc9e5d?q=75&fm=jpg",
+            "regular": "https://images.unsplash.com/photo-1417325384643-aac51acc9e5d?q=75&fm=jpg&w=1080&fit=max",
+            "small": "https://images.unsplash.com/photo-1417325384643-aac51acc9e5d?q=75&fm=jpg&w=400&fit=max",
+            "thumb": "https://images.unsplash.com/photo-1417325384643-aac51acc9e5d?q=75&fm=jpg&w=200&fit=max"
+        },
+        "links": {
+            "self": "https://api.unsplash.com/photos/Dwu85P9SOIk",
+            "html": "https://unsplash.com/photos/Dwu85P9SOIk",
+            "download_location": "https://api.unsplash.com/photos/Dwu85P9SOIk/download"
+        },
+        "user": { "id": "QPxL2MGqfrw", "username": "exampleuser", "name": "Joe Example" },
+        "tags": [{ "title": "man" }, { "title": "drinking" }]
+    }"##;
+
+    fn config(url: &str) -> AppConfig {
+        AppConfig {
+            unsplash_api_key: Some(" abc123 ".to_string()),
+            unsplash_api_url: Some(url.to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn query(params: &[(&str, &str)]) -> SearchQuery {
+        let params: HashMap<String, String> = params.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        SearchQuery::from_params(params).unwrap()
+    }
+
+    #[test]
+    fn search_parses_results_and_pages() {
+        let body = format!(r#"{{ "total": 133, "total_pages": 7, "results": [{}] }}"#, PHOTO);
+        let server = MockServer::start(vec![(200, body)]);
+        let page = UnsplashSource::search_with(&config(&server.url), &query(&[("topic", "office"), ("page", "3"), ("orientation", "Landscape")])).unwrap();
+
+        assert_eq!(page.meta.current_page, 3);
+        assert_eq!(page.meta.last_page, 7);
+        assert_eq!(page.meta.total, 133);
+        assert_eq!(page.meta.per_page, PER_PAGE);
+        assert_eq!(page.meta.query, "office");
+        assert_eq!(page.data.len(), 1);
+        let image = &page.data[0];
+        assert_eq!(image.source, "unsplash");
+        assert_eq!(image.id, "Dwu85P9SOIk");
+        assert_eq!(image.url, "https://unsplash.com/photos/Dwu85P9SOIk");
+        assert!(image.original_url.ends_with("?q=75&fm=jpg"));
+        assert!(image.thumbnail_url.ends_with("&w=400&fit=max"));
+        assert_eq!(image.author.as_deref(), Some("Joe Example"));
+        assert_eq!(image.colors, vec!["#6E633A"]);
+        assert_eq!(image.tags, vec!["man", "drinking"]);
+
+        let requests = server.requests();
+        assert!(requests[0].starts_with("GET /search/photos?query=office&page=3&per_page=24&orientation=landscape HTTP/1.1\r\n"));
+        let headers = requests[0].to_lowercase();
+        assert!(headers.contains("authorization: client-id abc123\r\n"));
+        assert!(headers.contains("accept-version: v1\r\n"));
+        assert!(headers.contains("user-agent: wallpaper_changer/"));
+    }
+
+    #[test]
+    fn search_without_results_still_has_a_page() {
+        let server = MockServer::start(vec![(200, r#"{ "total": 0, "total_pages": 0, "results": [] }"#.to_string())]);
+        let page = UnsplashSource::search_with(&config(&server.url), &query(&[("topic", "nothing")])).unwrap();
+        assert!(page.data.is_empty());
+        assert_eq!(page.meta.current_page, 1);
+        assert_eq!(page.meta.last_page, 1);
+    }
+
+    #[test]
+    fn get_by_id() {
+        let server = MockServer::start(vec![(200, PHOTO.to_string()), (404, r#"{ "errors": ["Couldn't find Photo"] }"#.to_string())]);
+        let image = UnsplashSource::get_by_id_with(&config(&server.url), "Dwu85P9SOIk").unwrap();
+        assert_eq!(image.id, "Dwu85P9SOIk");
+        assert_eq!((image.width, image.height), (2448, 3264));
+
+        let error = UnsplashSource::get_by_id_with(&config(&server.url), "missing").unwrap_err();
+        assert!(matches!(error, SourceError::NotFound(_)));
+        let requests = server.requests();
+        assert!(requests[0].starts_with("GET /photos/Dwu85P9SOIk HTTP/1.1\r\n"));
+        assert!(requests[1].starts_with("GET /photos/missing HTTP/1.1\r\n"));
+    }
+
+    #[test]
+    fn rejected_key() {
+        let server = MockServer::start(vec![(401, r#"{ "errors": ["OAuth error: The access token is invalid"] }"#.to_string())]);
+        let error = UnsplashSource::search_with(&config(&server.url), &query(&[("topic", "cats")])).unwrap_err();
+        assert!(error.to_string().contains("rejected the API key (HTTP 401)"));
+    }
+
+    #[test]
+    fn missing_key_and_topic() {
+        // Nothing listens here, the checks have to fail before any request is made
+        let mut config = config("http://127.0.0.1:9");
+        let error = UnsplashSource::search_with(&config, &query(&[("topic", " ")])).unwrap_err();
+        assert!(matches!(error, SourceError::InvalidQuery(_)));
+
+        let error = UnsplashSource::search_with(&config, &query(&[("topic", "cats"), ("color", "pink")])).unwrap_err();
+        assert!(matches!(error, SourceError::InvalidQuery(_)));
+
+        config.unsplash_api_key = Some("  ".to_string());
+        let error = UnsplashSource::search_with(&config, &query(&[("topic", "cats")])).unwrap_err();
+        assert!(matches!(&error, SourceError::InvalidQuery(message) if message.contains("unsplash_api_key")));
+        config.unsplash_api_key = None;
+        let error = UnsplashSource::get_by_id_with(&config, "Dwu85P9SOIk").unwrap_err();
+        assert!(matches!(&error, SourceError::InvalidQuery(message) if message.contains("unsplash_api_key")));
+    }
+}
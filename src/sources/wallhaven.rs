@@ -0,0 +1,109 @@
+use crate::models::wallhaven::{WHCollection, WHCollectionListingResponse, WHCollectionsResponse, WHDirectModel, WHSearchParams, WHTag, WHTagResponse};
+use crate::models::wallpaper::{SearchPage, Wallpaper};
+use crate::utils;
+use super::{ImageSource, SearchQuery, SourceError};
+
+pub struct WallhavenSource;
+
+impl WallhavenSource {
+    // Picks the Wallhaven filters out of the neutral query's options
+    pub fn to_params(query: &SearchQuery) -> WHSearchParams {
+        let option = |key: &str| query.option(key).map(str::to_string);
+        WHSearchParams {
+            topic: query.topic.clone(),
+            page: query.page,
+            categories: option("categories"),
+            purity: option("purity"),
+            sorting: option("sorting"),
+            order: option("order"),
+            top_range: option("topRange"),
+            atleast: option("atleast"),
+            resolutions: option("resolutions"),
+            ratios: option("ratios"),
+            colors: option("colors"),
+            seed: option("seed"),
+        }
+    }
+
+    pub fn tag(id: u64) -> Result<WHTag, SourceError> {
+        let url = format!("{}/{}/{}", utils::WALLHAVEN_SEARCH_API, utils::WALLHAVEN_TAG_PATH, id);
+        let response = utils::wallhaven_request(&url)
+            .call()
+            .map_err(|e| match SourceError::from(e) {
+                SourceError::NotFound(_) => SourceError::NotFound(format!("No Wallhaven tag with id {}", id)),
+                e => e,
+            })?
+            .body_mut()
+            .read_json::<WHTagResponse>()?;
+        Ok(response.data)
+    }
+
+    // Wallhaven's own "similar wallpapers" search
+    pub fn like_topic(id: &str) -> String {
+        format!("like:{}", id)
+    }
+
+    // Public collections of a user, or the API key owner's own collections (private ones included) without a username
+    pub fn collections(username: Option<&str>) -> Result<Vec<WHCollection>, SourceError> {
+        let url = match username {
+            Some(username) => format!("{}/{}/{}", utils::WALLHAVEN_SEARCH_API, utils::WALLHAVEN_COLLECTIONS_PATH, valid_username(username)?),
+            None => format!("{}/{}", utils::WALLHAVEN_SEARCH_API, utils::WALLHAVEN_COLLECTIONS_PATH),
+        };
+        let response = utils::wallhaven_request(&url)
+            .call()?
+            .body_mut()
+            .read_json::<WHCollectionsResponse>()?;
+        Ok(response.data)
+    }
+
+    // One page of 24 images, private collections need the owner's API key
+    pub fn collection_page(username: &str, id: u64, page: u32) -> Result<WHCollectionListingResponse, SourceError> {
+        let url = utils::api_url(
+            utils::WALLHAVEN_SEARCH_API,
+            &format!("{}/{}/{}", utils::WALLHAVEN_COLLECTIONS_PATH, valid_username(username)?, id),
+            &[("page", page.to_string())],
+        );
+        let response = utils::wallhaven_request(&url)
+            .call()?
+            .body_mut()
+            .read_json::<WHCollectionListingResponse>()?;
+        Ok(response)
+    }
+}
+
+// Usernames go into the URL path
+fn valid_username(username: &str) -> Result<&str, SourceError> {
+    let username = username.trim();
+    if username.is_empty() || !username.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+        return Err(SourceError::InvalidQuery(format!("Invalid Wallhaven username '{}'", username)));
+    }
+    Ok(username)
+}
+
+impl ImageSource for WallhavenSource {
+    fn name(&self) -> &'static str {
+        "wallhaven"
+    }
+
+    fn search(&self, query: &SearchQuery) -> Result<SearchPage, SourceError> {
+        let search_query = WallhavenSource::to_params(query)
+            .to_query()
+            .map_err(SourceError::InvalidQuery)?;
+
+        let url = utils::create_search_query(&search_query);
+        println!("Search query: {}", url);
+
+        // Returns the page of 24 results
+        let response = utils::search_topic(&url)?;
+        Ok(response.into())
+    }
+
+    fn get_by_id(&self, id: &str) -> Result<Wallpaper, SourceError> {
+        let url = format!("{}/{}", utils::WALLHAVEN_DIRECT_ID, id);
+        let response = utils::wallhaven_request(&url)
+            .call()?
+            .body_mut()
+            .read_json::<WHDirectModel>()?;
+        Ok(response.data.into())
+    }
+}
@@ -0,0 +1,192 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use crate::{config, scheduler, utils, wallpaper};
+
+// The wallpaper the app last set, kept in current.json so it survives restarts
+// A watcher compares it with what the desktop reports to notice changes made outside the app
+
+const STATE_FILE_NAME: &str = "current.json";
+const DEFAULT_WATCH_INTERVAL_SECONDS: u64 = 30;
+
+static LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct CurrentState {
+    // Set on every monitor, None until the app has set a wallpaper
+    pub path: Option<String>,
+    // Set on single monitors since then, keyed by output name
+    #[serde(default)]
+    pub outputs: BTreeMap<String, String>,
+    pub changed_at: Option<u64>,
+    // What the desktop reported on the last check, so an outside change is only flagged once
+    #[serde(default)]
+    pub desktop_value: Option<String>,
+    #[serde(default)]
+    pub external_change: Option<ExternalChange>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ExternalChange {
+    pub detected_at: u64,
+    // As reported by the desktop, usually a path
+    pub value: String,
+}
+
+pub fn get_state_path() -> PathBuf {
+    utils::get_user_data_directory().join(STATE_FILE_NAME)
+}
+
+pub fn load() -> CurrentState {
+    let _guard = LOCK.lock().unwrap();
+    utils::read_json_file(&get_state_path())
+}
+
+fn update<F: FnOnce(&mut CurrentState)>(change: F) {
+    let _guard = LOCK.lock().unwrap();
+    let mut state: CurrentState = utils::read_json_file(&get_state_path());
+    change(&mut state);
+    if let Err(e) = utils::write_json_file(&get_state_path(), &state) {
+        eprintln!("Failed to save current wallpaper state: {}", e);
+    }
+}
+
+// Called by the wallpaper module after every successful change
+pub fn set_current(path: &str, output: Option<&str>) {
+    update(|state| {
+        match output {
+            Some(output) => {
+                state.outputs.insert(output.to_string(), path.to_string());
+            },
+            None => {
+                state.path = Some(path.to_string());
+                state.outputs.clear();
+            }
+        }
+        state.changed_at = Some(scheduler::now());
+        // Whatever the desktop showed before belonged to the previous wallpaper
+        state.desktop_value = None;
+        state.external_change = None;
+    });
+}
+
+// Puts the last wallpaper back, e.g. after a reboot or the desktop resetting its background
+pub fn restore() {
+    let state = load();
+    if let Some(path) = &state.path {
+        restore_one(path, None);
+    }
+    for (output, path) in &state.outputs {
+        restore_one(path, Some(output));
+    }
+}
+
+fn restore_one(path: &str, output: Option<&str>) {
+    if !Path::new(path).exists() {
+        eprintln!("Restore: {} no longer exists", path);
+        return;
+    }
+    match wallpaper::reapply(path, output) {
+        Ok(_) => println!("Restore: set {} again", path),
+        Err(e) => eprintln!("Restore: failed to set {}: {}", path, e)
+    }
+}
+
+// Reads the desktop's wallpaper and flags it when it moved away from what the app set
+pub fn check_external_change() -> std::io::Result<()> {
+    let before = load();
+    if before.path.is_none() && before.outputs.is_empty() {
+        return Ok(());
+    }
+    let Some(value) = wallpaper::current()? else {
+        return Ok(());
+    };
+    update(|state| note_desktop_value(state, &before, value));
+    Ok(())
+}
+
+// `before` is the state from just before the desktop was read
+fn note_desktop_value(state: &mut CurrentState, before: &CurrentState, value: String) {
+    // The app set another wallpaper while the desktop was being read, so the reading may still show the old one
+    if state.path != before.path || state.outputs != before.outputs || state.changed_at != before.changed_at {
+        return;
+    }
+    // Checked against what the app set rather than an earlier reading, which may already have been changed elsewhere
+    if !set_by_app(state, &value) && state.desktop_value.as_deref() != Some(value.as_str()) {
+        println!("Watcher: wallpaper changed outside the app to {}", value);
+        state.external_change = Some(ExternalChange { detected_at: scheduler::now(), value: value.clone() });
+    }
+    state.desktop_value = Some(value);
+}
+
+// Backends that track each monitor report one image per monitor, separated by ", "
+// Spanned wallpapers show images generated from the one that was set
+fn set_by_app(state: &CurrentState, value: &str) -> bool {
+    let spanned = wallpaper::span::get_spanned_directory();
+    value.split(", ").all(|image| {
+        state.path.as_deref() == Some(image)
+            || state.outputs.values().any(|path| path == image)
+            || Path::new(image).starts_with(&spanned)
+    })
+}
+
+// Polls the desktop every "watch_interval_seconds" (default 30, 0 turns it off)
+pub async fn watch() {
+    let seconds = config::load().watch_interval_seconds.unwrap_or(DEFAULT_WATCH_INTERVAL_SECONDS);
+    if seconds == 0 {
+        return;
+    }
+    let mut ticker = tokio::time::interval(Duration::from_secs(seconds));
+    let mut last_error: Option<String> = None;
+
+    loop {
+        ticker.tick().await;
+        let result = tokio::task::spawn_blocking(check_external_change).await;
+        let error = match result {
+            Ok(Ok(())) => None,
+            Ok(Err(e)) => Some(e.to_string()),
+            Err(e) => Some(e.to_string()),
+        };
+        // Only report an error once rather than on every check
+        if error.is_some() && error != last_error {
+            eprintln!("Watcher: could not read the current wallpaper: {}", error.as_deref().unwrap_or_default());
+        }
+        last_error = error;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_by_app_matches_what_was_set() {
+        let mut state = CurrentState { path: Some("/w/wallhaven-abc.jpg".to_string()), ..Default::default() };
+        assert!(set_by_app(&state, "/w/wallhaven-abc.jpg"));
+        assert!(!set_by_app(&state, "/usr/share/backgrounds/default.png"));
+
+        let slice = wallpaper::span::get_spanned_directory().join("wallhaven-abc-DP-1.jpg");
+        assert!(set_by_app(&state, &slice.to_string_lossy()));
+
+        state.outputs.insert("DP-1".to_string(), "/w/pexels-1.jpg".to_string());
+        assert!(set_by_app(&state, "/w/wallhaven-abc.jpg, /w/pexels-1.jpg"));
+        assert!(!set_by_app(&state, "/w/wallhaven-abc.jpg, /home/me/other.jpg"));
+    }
+
+    #[test]
+    fn readings_taken_during_a_change_are_ignored() {
+        let before = CurrentState { path: Some("/w/old.jpg".to_string()), changed_at: Some(100), ..Default::default() };
+
+        let mut state = before.clone();
+        note_desktop_value(&mut state, &before, "/home/me/other.jpg".to_string());
+        assert_eq!(state.external_change.map(|change| change.value).as_deref(), Some("/home/me/other.jpg"));
+
+        // The app switched to new.jpg while the desktop still reported old.jpg
+        let mut state = CurrentState { path: Some("/w/new.jpg".to_string()), changed_at: Some(101), ..Default::default() };
+        note_desktop_value(&mut state, &before, "/w/old.jpg".to_string());
+        assert!(state.external_change.is_none());
+        assert!(state.desktop_value.is_none());
+    }
+}
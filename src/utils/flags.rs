@@ -4,3 +4,6 @@ pub const TOPIC: &str = "--topic";
 pub const PAGE: &str = "--page";
 pub const VERSION: &str = "--version";
 pub const HELP: &str = "--help";
+// --daily [bing|apod|apod,bing] sets the daily image and keeps checking for a new one, add --once to exit afterwards
+pub const DAILY: &str = "--daily";
+pub const ONCE: &str = "--once";
@@ -1,17 +1,24 @@
 use std::error::Error;
-use crate::models;
-use std::path::PathBuf;
+use crate::{config, models};
+use std::path::{Path, PathBuf};
 use crate::utils::os::{ get_operating_system, OperatingSystem};
 
 
+#[allow(dead_code)] // Only --daily is wired up so far
 pub mod flags;
 pub mod os;
 
 
 pub const WALLHAVEN_DIRECT_ID: &str = "https://wallhaven.cc/api/v1/w";
 pub const WALLHAVEN_SEARCH_API: &str = "https://wallhaven.cc/api/v1";
-pub const WALLHAVEN_SEARCH_PARAM: &str = "search?q=";
-pub const WALLHAVEN_SEARCH_PAGE: &str = "page";
+pub const WALLHAVEN_SEARCH_PATH: &str = "search";
+pub const WALLHAVEN_SETTINGS_PATH: &str = "settings";
+pub const WALLHAVEN_COLLECTIONS_PATH: &str = "collections";
+pub const WALLHAVEN_TAG_PATH: &str = "tag";
+pub const WALLHAVEN_API_KEY_HEADER: &str = "X-API-Key";
+pub const UNSPLASH_API: &str = "https://api.unsplash.com";
+pub const PEXELS_API: &str = "https://api.pexels.com/v1";
+pub const REDDIT_API: &str = "https://www.reddit.com";
 
 pub fn get_file_extension(file_type: &str) -> &str {
     match file_type.to_lowercase().as_str() {
@@ -19,59 +26,71 @@ pub fn get_file_extension(file_type: &str) -> &str {
         "image/png" => "png",
         "image/gif" => "gif",
         "image/webp" => "webp",
+        "image/bmp" => "bmp",
         _ => "jpg"  // default to jpg if unknown
     }
 }
 
-pub fn create_seach_query_object(topic_value: Option<String>, current_page: String) -> Result<String, Result<(), Box<dyn Error + Send + Sync>>> {
-    let search_query: String = match topic_value {
-        Some(topic) => format!(
-            "{}/{}{}&{}={}",
-            WALLHAVEN_SEARCH_API,
-            WALLHAVEN_SEARCH_PARAM,
-            topic,
-            WALLHAVEN_SEARCH_PAGE,
-            current_page
-        ),
-        None => {
-            eprintln!("Error: No topic provided.");
-            return Err(Ok(()));  // Exit the function early
-        }
-    };
-    Ok(search_query)
+pub fn create_search_query(query: &models::wallhaven::WHSearchQuery) -> String {
+    // Example: https://wallhaven.cc/api/v1/search?q=cats&sorting=toplist&topRange=1M&page=1
+    let encoded: String = form_urlencoded::Serializer::new(String::new())
+        .extend_pairs(query.to_query_pairs())
+        .finish();
+
+    format!(
+        "{}/{}?{}",
+        WALLHAVEN_SEARCH_API,
+        WALLHAVEN_SEARCH_PATH,
+        encoded
+    )
 }
 
-pub fn search_topic(search_query: &String) -> Result<models::wallhaven::WHSearchResponse, Box<dyn Error + Send + Sync>> {
-    let response = ureq::get(search_query.as_str())
+pub fn api_request(url: &str) -> ureq::RequestBuilder<ureq::typestate::WithoutBody> {
+    ureq::get(url)
         .header("User-Agent", format!("wallpaper_changer/{}", env!("CARGO_PKG_VERSION")))
-        .call()?
-        .body_mut()
-        .read_json::<models::wallhaven::WHSearchResponse>()?;
-    Ok(response)
 }
 
-pub fn create_search_object_response(search_text: String, current_page_inner: u16) -> Option<models::wallhaven::WHSearchResponse> {
-    // Example: https://wallhaven.cc/api/v1/search?q=cats&page=1
-    let search_query = match create_seach_query_object(Some(search_text), current_page_inner.to_string()) {
-        Ok(value) => value,
-        Err(_) => String::new()
-    };
+// Joins base + path and appends the url encoded params, e.g. https://api.pexels.com/v1/search?query=cats
+pub fn api_url(base: &str, path: &str, params: &[(&str, String)]) -> String {
+    let url = format!("{}/{}", base.trim_end_matches('/'), path);
+    if params.is_empty() {
+        return url;
+    }
+    let encoded: String = form_urlencoded::Serializer::new(String::new())
+        .extend_pairs(params)
+        .finish();
+    format!("{}?{}", url, encoded)
+}
 
-    println!("Search query: {}", search_query);
+// Every Wallhaven API call goes through here so the stored API key is attached when there is one
+pub fn wallhaven_request(url: &str) -> ureq::RequestBuilder<ureq::typestate::WithoutBody> {
+    wallhaven_request_with_key(url, config::load().wallhaven_api_key.as_deref())
+}
 
-    // Get response back from API with query
-    // Returns the page of 24 results
-    let response: Option<models::wallhaven::WHSearchResponse> = match search_topic(&search_query) {
-        Ok(response) => {
-            // println!("Got response: {:?}", response);
-            Some(response)
-        }
-        Err(e) => {
-            eprintln!("Error: {}", e);
-            None
-        }
-    };
-    response
+pub fn wallhaven_request_with_key(url: &str, api_key: Option<&str>) -> ureq::RequestBuilder<ureq::typestate::WithoutBody> {
+    let request = api_request(url);
+    match api_key {
+        Some(key) if !key.trim().is_empty() => request.header(WALLHAVEN_API_KEY_HEADER, key.trim()),
+        _ => request
+    }
+}
+
+// Fetches the account's default browsing settings, this fails with 401 if the key is invalid
+pub fn get_wallhaven_settings(api_key: &str) -> Result<models::wallhaven::WHUserSettings, Box<dyn Error + Send + Sync>> {
+    let url = format!("{}/{}", WALLHAVEN_SEARCH_API, WALLHAVEN_SETTINGS_PATH);
+    let response = wallhaven_request_with_key(&url, Some(api_key))
+        .call()?
+        .body_mut()
+        .read_json::<models::wallhaven::WHUserSettingsResponse>()?;
+    Ok(response.data)
+}
+
+pub fn search_topic(search_query: &str) -> Result<models::wallhaven::WHSearchResponse, Box<dyn Error + Send + Sync>> {
+    let response = wallhaven_request(search_query)
+        .call()?
+        .body_mut()
+        .read_json::<models::wallhaven::WHSearchResponse>()?;
+    Ok(response)
 }
 
 pub fn get_app_data_directory() -> PathBuf {
@@ -148,7 +167,7 @@ pub fn get_collections_directory() -> PathBuf {
     get_user_data_directory().join("collections")
 }
 
-pub fn ensure_dir(path: &PathBuf) -> std::io::Result<()> {
+pub fn ensure_dir(path: &Path) -> std::io::Result<()> {
     if !path.exists() {
         std::fs::create_dir_all(path)?;
     }
@@ -165,4 +184,37 @@ pub fn sanitize_tag_name(name: &str) -> String {
     s
 }
 
+pub fn is_image(path: &Path) -> bool {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some(ext) => matches!(ext.to_lowercase().as_str(), "jpg" | "jpeg" | "png" | "gif" | "webp" | "bmp"),
+        None => false
+    }
+}
 
+// Missing or unreadable files fall back to the default, so a broken file never stops the app
+pub fn read_json_file<T: serde::de::DeserializeOwned + Default>(path: &Path) -> T {
+    if !path.exists() {
+        return T::default();
+    }
+    match std::fs::read_to_string(path) {
+        Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|e| {
+            eprintln!("Failed to parse {}: {}", path.display(), e);
+            T::default()
+        }),
+        Err(e) => {
+            eprintln!("Failed to read {}: {}", path.display(), e);
+            T::default()
+        }
+    }
+}
+
+// Written to a temporary file first so a crash never leaves half a file behind
+pub fn write_json_file<T: serde::Serialize>(path: &Path, value: &T) -> Result<(), Box<dyn Error + Send + Sync>> {
+    if let Some(parent) = path.parent() {
+        ensure_dir(parent)?;
+    }
+    let temp_path = path.with_extension("json.tmp");
+    std::fs::write(&temp_path, serde_json::to_string_pretty(value)?)?;
+    std::fs::rename(&temp_path, path)?;
+    Ok(())
+}
@@ -10,19 +10,19 @@ pub enum OperatingSystem {
 pub fn get_operating_system() -> OperatingSystem
 {
     if cfg!(target_os = "windows") {
-        return OperatingSystem::Windows
+        OperatingSystem::Windows
     } else if cfg!(target_os = "macos") {
         // Check the architecture
         if cfg!(target_arch = "aarch64") {
-            return OperatingSystem::MacArm
+            OperatingSystem::MacArm
         } else if cfg!(target_arch = "x86_64") {
-            return OperatingSystem::MacIntel
+            OperatingSystem::MacIntel
         } else {
-            return OperatingSystem::Unknown
+            OperatingSystem::Unknown
         }
     } else if cfg!(target_os = "linux") {
-        return OperatingSystem::Linux
+        OperatingSystem::Linux
     } else {
-        return OperatingSystem::Unknown
+        OperatingSystem::Unknown
     }
 }
\ No newline at end of file
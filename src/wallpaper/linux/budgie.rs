@@ -0,0 +1,30 @@
+use std::process::Output;
+use crate::wallpaper::FitMode;
+use super::gnome::Gnome;
+use super::system::CommandRunner;
+use super::WallpaperBackend;
+
+// Budgie draws the GNOME background settings itself, so it shares GNOME's schema
+pub struct Budgie;
+
+impl WallpaperBackend for Budgie {
+    fn name(&self) -> &'static str {
+        "budgie"
+    }
+
+    fn set(&self, runner: &dyn CommandRunner, path: &str, mode: FitMode) -> std::io::Result<Output> {
+        Gnome.set(runner, path, mode)
+    }
+
+    fn set_spanned(&self, runner: &dyn CommandRunner, path: &str) -> std::io::Result<Output> {
+        Gnome.set_spanned(runner, path)
+    }
+
+    fn current(&self, runner: &dyn CommandRunner) -> std::io::Result<Option<String>> {
+        Gnome.current(runner)
+    }
+
+    fn set_lock_screen(&self, runner: &dyn CommandRunner, path: &str, mode: FitMode) -> std::io::Result<Output> {
+        Gnome.set_lock_screen(runner, path, mode)
+    }
+}
@@ -0,0 +1,28 @@
+use std::process::Output;
+use crate::wallpaper::FitMode;
+use super::gnome::{get_setting, picture_options};
+use super::system::CommandRunner;
+use super::WallpaperBackend;
+
+// Cinnamon forked the GNOME schema under its own name
+pub struct Cinnamon;
+
+impl WallpaperBackend for Cinnamon {
+    fn name(&self) -> &'static str {
+        "cinnamon"
+    }
+
+    fn set(&self, runner: &dyn CommandRunner, path: &str, mode: FitMode) -> std::io::Result<Output> {
+        runner.run("gsettings", &["set", "org.cinnamon.desktop.background", "picture-options", picture_options(mode)])?;
+        runner.run("gsettings", &["set", "org.cinnamon.desktop.background", "picture-uri", &format!("file://{}", path)])
+    }
+
+    fn set_spanned(&self, runner: &dyn CommandRunner, path: &str) -> std::io::Result<Output> {
+        runner.run("gsettings", &["set", "org.cinnamon.desktop.background", "picture-options", "spanned"])?;
+        runner.run("gsettings", &["set", "org.cinnamon.desktop.background", "picture-uri", &format!("file://{}", path)])
+    }
+
+    fn current(&self, runner: &dyn CommandRunner) -> std::io::Result<Option<String>> {
+        get_setting(runner, "org.cinnamon.desktop.background", "picture-uri")
+    }
+}
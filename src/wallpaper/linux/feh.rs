@@ -0,0 +1,110 @@
+use std::fs;
+use std::process::Output;
+use crate::wallpaper::FitMode;
+use super::system::CommandRunner;
+use super::WallpaperBackend;
+
+const FEHBG_FILE_NAME: &str = ".fehbg";
+
+// feh writes ~/.fehbg on every change, WMs restore it by running that script on startup
+pub struct Feh;
+
+impl WallpaperBackend for Feh {
+    fn name(&self) -> &'static str {
+        "feh"
+    }
+
+    fn set(&self, runner: &dyn CommandRunner, path: &str, mode: FitMode) -> std::io::Result<Output> {
+        runner.run("feh", &[feh_mode(mode), path])
+    }
+
+    // feh takes one image per screen in Xinerama order, so the other screens keep the images from ~/.fehbg
+    fn supports_outputs(&self) -> bool {
+        true
+    }
+
+    fn set_for_output(&self, runner: &dyn CommandRunner, output: &str, path: &str, mode: FitMode) -> std::io::Result<Output> {
+        let listing = runner.run("xrandr", &["--listmonitors"])?;
+        let screens = parse_listmonitors(&String::from_utf8_lossy(&listing.stdout));
+        let index = screens.iter().position(|name| name == output).ok_or_else(|| std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("Output {} is not an active monitor", output)
+        ))?;
+
+        let current = dirs::home_dir()
+            .and_then(|home| fs::read_to_string(home.join(FEHBG_FILE_NAME)).ok())
+            .map(|script| fehbg_images(&script))
+            .unwrap_or_default();
+
+        let images: Vec<String> = (0..screens.len())
+            .map(|i| if i == index {
+                path.to_string()
+            } else {
+                current.get(i).cloned().unwrap_or_else(|| path.to_string())
+            })
+            .collect();
+
+        let mut args: Vec<&str> = vec![feh_mode(mode)];
+        args.extend(images.iter().map(String::as_str));
+        runner.run("feh", &args)
+    }
+
+    // One image per screen, listed in Xinerama order
+    fn current(&self, _runner: &dyn CommandRunner) -> std::io::Result<Option<String>> {
+        let images = dirs::home_dir()
+            .and_then(|home| fs::read_to_string(home.join(FEHBG_FILE_NAME)).ok())
+            .map(|script| fehbg_images(&script))
+            .unwrap_or_default();
+        Ok(Some(images.join(", ")).filter(|images| !images.is_empty()))
+    }
+}
+
+fn feh_mode(mode: FitMode) -> &'static str {
+    match mode {
+        FitMode::Fill => "--bg-fill",
+        FitMode::Scale => "--bg-max",
+        FitMode::Center => "--bg-center",
+        FitMode::Tile => "--bg-tile",
+    }
+}
+
+// e.g. " 0: +*DP-1 2560/597x1440/336+0+0  DP-1", the output name is the last column
+pub fn parse_listmonitors(text: &str) -> Vec<String> {
+    text.lines()
+        .skip(1)
+        .filter_map(|line| line.split_whitespace().last())
+        .map(str::to_string)
+        .collect()
+}
+
+// feh quotes every image path in single quotes, with ' written as '\''
+pub fn fehbg_images(script: &str) -> Vec<String> {
+    let Some(line) = script.lines().rev().find(|line| line.trim_start().starts_with("feh ")) else {
+        return Vec::new();
+    };
+
+    let mut images = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, in_quotes) {
+            ('\'', true) => in_quotes = false,
+            ('\'', false) => in_quotes = true,
+            // The escaped quote sits between two quoted sections
+            ('\\', false) if chars.peek() == Some(&'\'') => {
+                chars.next();
+                current.push('\'');
+            },
+            (c, true) => current.push(c),
+            (c, false) if c.is_whitespace() && !current.is_empty() => {
+                images.push(std::mem::take(&mut current));
+            },
+            _ => {}
+        }
+    }
+    if !current.is_empty() {
+        images.push(current);
+    }
+    images
+}
@@ -0,0 +1,66 @@
+use std::process::Output;
+use crate::wallpaper::FitMode;
+use super::system::CommandRunner;
+use super::WallpaperBackend;
+
+const BACKGROUND_SCHEMA: &str = "org.gnome.desktop.background";
+const SCREENSAVER_SCHEMA: &str = "org.gnome.desktop.screensaver";
+
+pub struct Gnome;
+
+impl WallpaperBackend for Gnome {
+    fn name(&self) -> &'static str {
+        "gnome"
+    }
+
+    fn set(&self, runner: &dyn CommandRunner, path: &str, mode: FitMode) -> std::io::Result<Output> {
+        let uri = format!("file://{}", path);
+
+        // Use gsettings to set the wallpaper
+        runner.run("gsettings", &["set", BACKGROUND_SCHEMA, "picture-options", picture_options(mode)])?;
+        let output = runner.run("gsettings", &["set", BACKGROUND_SCHEMA, "picture-uri", &uri])?;
+
+        // GNOME 42+ shows picture-uri-dark while dark mode is on, older versions don't have the key
+        if let Err(e) = runner.run("gsettings", &["set", BACKGROUND_SCHEMA, "picture-uri-dark", &uri]) {
+            eprintln!("Could not set picture-uri-dark (GNOME older than 42?): {}", e);
+        }
+        Ok(output)
+    }
+
+    fn set_spanned(&self, runner: &dyn CommandRunner, path: &str) -> std::io::Result<Output> {
+        let uri = format!("file://{}", path);
+        runner.run("gsettings", &["set", BACKGROUND_SCHEMA, "picture-options", "spanned"])?;
+        let output = runner.run("gsettings", &["set", BACKGROUND_SCHEMA, "picture-uri", &uri])?;
+        if let Err(e) = runner.run("gsettings", &["set", BACKGROUND_SCHEMA, "picture-uri-dark", &uri]) {
+            eprintln!("Could not set picture-uri-dark (GNOME older than 42?): {}", e);
+        }
+        Ok(output)
+    }
+
+    fn current(&self, runner: &dyn CommandRunner) -> std::io::Result<Option<String>> {
+        get_setting(runner, BACKGROUND_SCHEMA, "picture-uri")
+    }
+
+    fn set_lock_screen(&self, runner: &dyn CommandRunner, path: &str, mode: FitMode) -> std::io::Result<Output> {
+        runner.run("gsettings", &["set", SCREENSAVER_SCHEMA, "picture-options", picture_options(mode)])?;
+        runner.run("gsettings", &["set", SCREENSAVER_SCHEMA, "picture-uri", &format!("file://{}", path)])
+    }
+}
+
+// Values of the picture-options key, shared by the GNOME derived desktops (Cinnamon, MATE, Budgie)
+pub fn picture_options(mode: FitMode) -> &'static str {
+    match mode {
+        FitMode::Fill => "zoom",
+        FitMode::Scale => "scaled",
+        FitMode::Center => "centered",
+        FitMode::Tile => "wallpaper",
+    }
+}
+
+// gsettings prints strings quoted, e.g. 'file:///home/me/image.jpg', URIs are returned as plain paths
+pub fn get_setting(runner: &dyn CommandRunner, schema: &str, key: &str) -> std::io::Result<Option<String>> {
+    let output = runner.run("gsettings", &["get", schema, key])?;
+    let value = String::from_utf8_lossy(&output.stdout).trim().trim_matches('\'').to_string();
+    let value = value.strip_prefix("file://").map(str::to_string).unwrap_or(value);
+    Ok(Some(value).filter(|value| !value.is_empty()))
+}
@@ -0,0 +1,45 @@
+use std::process::Output;
+use crate::wallpaper::FitMode;
+use super::system::CommandRunner;
+use super::WallpaperBackend;
+
+// hyprpaper is driven through hyprctl, images have to be preloaded before they can be shown
+pub struct Hyprpaper;
+
+impl WallpaperBackend for Hyprpaper {
+    fn name(&self) -> &'static str {
+        "hyprpaper"
+    }
+
+    fn set(&self, runner: &dyn CommandRunner, path: &str, mode: FitMode) -> std::io::Result<Output> {
+        // An empty monitor name applies the wallpaper to every monitor
+        apply(runner, "", path, mode)
+    }
+
+    fn supports_outputs(&self) -> bool {
+        true
+    }
+
+    fn set_for_output(&self, runner: &dyn CommandRunner, output: &str, path: &str, mode: FitMode) -> std::io::Result<Output> {
+        apply(runner, output, path, mode)
+    }
+}
+
+fn apply(runner: &dyn CommandRunner, monitor: &str, path: &str, mode: FitMode) -> std::io::Result<Output> {
+    runner.run("hyprctl", &["hyprpaper", "preload", path])?;
+    let output = runner.run("hyprctl", &["hyprpaper", "wallpaper", &format!("{},{}{}", monitor, hyprpaper_mode(mode), path)])?;
+    // Free the previous images so memory doesn't grow with every change
+    if let Err(e) = runner.run("hyprctl", &["hyprpaper", "unload", "unused"]) {
+        eprintln!("Failed to unload unused hyprpaper images: {}", e);
+    }
+    Ok(output)
+}
+
+// hyprpaper takes the mode as a prefix on the path, it has no centre mode so that falls back to fit
+fn hyprpaper_mode(mode: FitMode) -> &'static str {
+    match mode {
+        FitMode::Fill => "",
+        FitMode::Scale | FitMode::Center => "contain:",
+        FitMode::Tile => "tile:",
+    }
+}
@@ -0,0 +1,67 @@
+use std::process::Output;
+use crate::wallpaper::FitMode;
+use super::system::CommandRunner;
+use super::WallpaperBackend;
+
+pub struct Kde;
+
+impl WallpaperBackend for Kde {
+    fn name(&self) -> &'static str {
+        "kde"
+    }
+
+    fn set(&self, runner: &dyn CommandRunner, path: &str, mode: FitMode) -> std::io::Result<Output> {
+        // The helper keeps whatever fill mode is already configured, so only use it for the Plasma default
+        if mode != FitMode::Fill || !runner.is_installed("plasma-apply-wallpaperimage") {
+            return evaluate_script(runner, path, mode);
+        }
+
+        // Plasma 5.26+ ships a small helper for exactly this
+        match runner.run("plasma-apply-wallpaperimage", &[path]) {
+            Ok(output) => Ok(output),
+            Err(e) => {
+                eprintln!("plasma-apply-wallpaperimage failed ({}), falling back to D-Bus", e);
+                evaluate_script(runner, path, mode)
+            }
+        }
+    }
+}
+
+// Older Plasma versions: ask plasmashell to run a script that updates every desktop
+fn evaluate_script(runner: &dyn CommandRunner, path: &str, mode: FitMode) -> std::io::Result<Output> {
+    let uri = format!("file://{}", path)
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"");
+
+    let script = format!(
+        "var allDesktops = desktops();
+for (var i = 0; i < allDesktops.length; i++) {{
+    var d = allDesktops[i];
+    d.wallpaperPlugin = \"org.kde.image\";
+    d.currentConfigGroup = Array(\"Wallpaper\", \"org.kde.image\", \"General\");
+    d.writeConfig(\"Image\", \"{}\");
+    d.writeConfig(\"FillMode\", {});
+}}",
+        uri,
+        fill_mode(mode)
+    );
+
+    runner.run("dbus-send", &[
+        "--session",
+        "--type=method_call",
+        "--dest=org.kde.plasmashell",
+        "/PlasmaShell",
+        "org.kde.PlasmaShell.evaluateScript",
+        &format!("string:{}", script),
+    ])
+}
+
+// Values of Plasma's Image.FillMode
+fn fill_mode(mode: FitMode) -> u8 {
+    match mode {
+        FitMode::Fill => 2,   // PreserveAspectCrop
+        FitMode::Scale => 1,  // PreserveAspectFit
+        FitMode::Center => 6, // Pad
+        FitMode::Tile => 3,   // Tile
+    }
+}
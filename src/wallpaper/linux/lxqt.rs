@@ -0,0 +1,26 @@
+use std::process::Output;
+use crate::wallpaper::FitMode;
+use super::system::CommandRunner;
+use super::WallpaperBackend;
+
+// The LXQt desktop is drawn by pcmanfm-qt, which talks to the running instance when called again
+pub struct Lxqt;
+
+impl WallpaperBackend for Lxqt {
+    fn name(&self) -> &'static str {
+        "lxqt"
+    }
+
+    fn set(&self, runner: &dyn CommandRunner, path: &str, mode: FitMode) -> std::io::Result<Output> {
+        runner.run("pcmanfm-qt", &["--set-wallpaper", path, "--wallpaper-mode", wallpaper_mode(mode)])
+    }
+}
+
+fn wallpaper_mode(mode: FitMode) -> &'static str {
+    match mode {
+        FitMode::Fill => "zoom",
+        FitMode::Scale => "fit",
+        FitMode::Center => "center",
+        FitMode::Tile => "tile",
+    }
+}
@@ -0,0 +1,28 @@
+use std::process::Output;
+use crate::wallpaper::FitMode;
+use super::gnome::{get_setting, picture_options};
+use super::system::CommandRunner;
+use super::WallpaperBackend;
+
+// MATE kept the GNOME 2 schema, which takes a plain file path rather than a URI
+pub struct Mate;
+
+impl WallpaperBackend for Mate {
+    fn name(&self) -> &'static str {
+        "mate"
+    }
+
+    fn set(&self, runner: &dyn CommandRunner, path: &str, mode: FitMode) -> std::io::Result<Output> {
+        runner.run("gsettings", &["set", "org.mate.background", "picture-options", picture_options(mode)])?;
+        runner.run("gsettings", &["set", "org.mate.background", "picture-filename", path])
+    }
+
+    fn set_spanned(&self, runner: &dyn CommandRunner, path: &str) -> std::io::Result<Output> {
+        runner.run("gsettings", &["set", "org.mate.background", "picture-options", "spanned"])?;
+        runner.run("gsettings", &["set", "org.mate.background", "picture-filename", path])
+    }
+
+    fn current(&self, runner: &dyn CommandRunner) -> std::io::Result<Option<String>> {
+        get_setting(runner, "org.mate.background", "picture-filename")
+    }
+}
@@ -0,0 +1,446 @@
+use std::process::Output;
+use crate::config;
+use crate::wallpaper::{span, ChangeOptions, FitMode};
+
+pub mod system;
+pub mod monitors;
+pub mod gnome;
+pub mod kde;
+pub mod sway;
+pub mod hyprpaper;
+pub mod swww;
+pub mod feh;
+pub mod nitrogen;
+pub mod xwallpaper;
+pub mod xfce;
+pub mod cinnamon;
+pub mod mate;
+pub mod lxqt;
+pub mod budgie;
+
+use system::{CommandRunner, Environment, SystemEnvironment, SystemRunner};
+
+// A way of setting the wallpaper on a particular desktop or compositor
+pub trait WallpaperBackend {
+    fn name(&self) -> &'static str;
+    fn set(&self, runner: &dyn CommandRunner, path: &str, mode: FitMode) -> std::io::Result<Output>;
+
+    // Whether set_for_output works, spanning uses it to give each monitor its own slice
+    fn supports_outputs(&self) -> bool {
+        false
+    }
+
+    // Backends that can't address a single output keep this default
+    fn set_for_output(&self, _runner: &dyn CommandRunner, _output: &str, _path: &str, _mode: FitMode) -> std::io::Result<Output> {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            format!("Per-monitor wallpapers are not supported by the {} backend", self.name())
+        ))
+    }
+
+    // Stretches one image across the whole monitor layout, the GNOME family's "spanned" option
+    fn set_spanned(&self, _runner: &dyn CommandRunner, _path: &str) -> std::io::Result<Output> {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            format!("Spanning one image across monitors is not supported by the {} backend", self.name())
+        ))
+    }
+
+    // What the desktop is showing right now, used to notice changes made outside the app
+    // None when the backend has no way to read it back
+    fn current(&self, _runner: &dyn CommandRunner) -> std::io::Result<Option<String>> {
+        Ok(None)
+    }
+
+    // Only some desktops keep a separate lock screen image
+    fn set_lock_screen(&self, _runner: &dyn CommandRunner, _path: &str, _mode: FitMode) -> std::io::Result<Output> {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            format!("Setting the lock screen is not supported by the {} backend", self.name())
+        ))
+    }
+}
+
+pub const BACKEND_NAMES: [&str; 13] = [
+    "gnome", "kde", "sway", "hyprpaper", "swww", "feh", "nitrogen", "xwallpaper",
+    "xfce", "cinnamon", "mate", "lxqt", "budgie",
+];
+
+// Standalone X11 setters in order of preference, used when no desktop environment manages the background
+const X11_SETTERS: [&str; 3] = ["feh", "xwallpaper", "nitrogen"];
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Desktop {
+    Gnome,
+    Kde,
+    Xfce,
+    Cinnamon,
+    Mate,
+    Lxqt,
+    Budgie,
+    Sway,
+    Hyprland,
+    // Any other wlroots based compositor (river, wayfire, niri...)
+    Wlroots,
+    // A plain X11 window manager (i3, bspwm, Openbox...) where the background is set by a separate tool
+    X11Wm,
+    Unknown,
+}
+
+// Compositors export their own IPC socket variables, which are more reliable than the desktop name.
+// Otherwise XDG_CURRENT_DESKTOP is a colon separated list (e.g. "ubuntu:GNOME"),
+// DESKTOP_SESSION is the older fallback (e.g. "plasma", "plasmawayland")
+pub fn detect_desktop_with(env: &dyn Environment) -> Desktop {
+    if env.is_set("HYPRLAND_INSTANCE_SIGNATURE") {
+        return Desktop::Hyprland;
+    }
+    if env.is_set("SWAYSOCK") {
+        return Desktop::Sway;
+    }
+    let current = env.var("XDG_CURRENT_DESKTOP").unwrap_or_default();
+    let session = env.var("DESKTOP_SESSION").unwrap_or_default();
+    match desktop_from(&current, &session) {
+        // An X session nobody claimed is most likely a bare window manager
+        Desktop::Unknown if env.is_set("DISPLAY") && !env.is_set("WAYLAND_DISPLAY") => Desktop::X11Wm,
+        desktop => desktop
+    }
+}
+
+pub fn desktop_from(current_desktop: &str, desktop_session: &str) -> Desktop {
+    for name in current_desktop.split(':').chain(std::iter::once(desktop_session)) {
+        match name.trim().to_lowercase().as_str() {
+            "kde" | "plasma" | "plasmawayland" | "plasmax11" => return Desktop::Kde,
+            // Budgie reports "Budgie:GNOME", so it has to be matched before GNOME
+            "budgie" | "budgie-desktop" => return Desktop::Budgie,
+            "gnome" | "gnome-xorg" | "gnome-wayland" | "ubuntu" | "ubuntu-wayland" | "pop" => return Desktop::Gnome,
+            "xfce" | "xfce4" | "xubuntu" => return Desktop::Xfce,
+            "x-cinnamon" | "cinnamon" | "cinnamon-wayland" => return Desktop::Cinnamon,
+            "mate" => return Desktop::Mate,
+            "lxqt" | "lubuntu" => return Desktop::Lxqt,
+            "sway" => return Desktop::Sway,
+            "hyprland" => return Desktop::Hyprland,
+            "river" | "wayfire" | "niri" | "labwc" | "dwl" | "wlroots" => return Desktop::Wlroots,
+            "i3" | "bspwm" | "openbox" | "awesome" | "dwm" | "herbstluftwm" | "qtile" | "xmonad"
+            | "fluxbox" | "icewm" | "spectrwm" | "leftwm" => return Desktop::X11Wm,
+            _ => {}
+        }
+    }
+    Desktop::Unknown
+}
+
+pub fn backend_by_name(name: &str) -> Option<Box<dyn WallpaperBackend>> {
+    match name.trim().to_lowercase().as_str() {
+        "gnome" => Some(Box::new(gnome::Gnome)),
+        "kde" | "plasma" => Some(Box::new(kde::Kde)),
+        "sway" | "swaybg" => Some(Box::new(sway::Sway)),
+        "hyprpaper" | "hyprland" => Some(Box::new(hyprpaper::Hyprpaper)),
+        "swww" => Some(Box::new(swww::Swww)),
+        "feh" => Some(Box::new(feh::Feh)),
+        "nitrogen" => Some(Box::new(nitrogen::Nitrogen)),
+        "xwallpaper" => Some(Box::new(xwallpaper::Xwallpaper)),
+        "xfce" => Some(Box::new(xfce::Xfce)),
+        "cinnamon" => Some(Box::new(cinnamon::Cinnamon)),
+        "mate" => Some(Box::new(mate::Mate)),
+        "lxqt" | "pcmanfm-qt" => Some(Box::new(lxqt::Lxqt)),
+        "budgie" => Some(Box::new(budgie::Budgie)),
+        _ => None
+    }
+}
+
+pub fn backend_for(desktop: Desktop, runner: &dyn CommandRunner) -> Option<Box<dyn WallpaperBackend>> {
+    match desktop {
+        Desktop::Gnome => Some(Box::new(gnome::Gnome)),
+        Desktop::Kde => Some(Box::new(kde::Kde)),
+        Desktop::Xfce => Some(Box::new(xfce::Xfce)),
+        Desktop::Cinnamon => Some(Box::new(cinnamon::Cinnamon)),
+        Desktop::Mate => Some(Box::new(mate::Mate)),
+        Desktop::Lxqt => Some(Box::new(lxqt::Lxqt)),
+        Desktop::Budgie => Some(Box::new(budgie::Budgie)),
+        Desktop::Sway => Some(Box::new(sway::Sway)),
+        Desktop::Hyprland => Some(Box::new(hyprpaper::Hyprpaper)),
+        Desktop::Wlroots => Some(Box::new(swww::Swww)),
+        Desktop::X11Wm => X11_SETTERS.iter()
+            .find(|program| runner.is_installed(program))
+            .and_then(|program| backend_by_name(program)),
+        // Still try GNOME when nothing else fits, as long as gsettings is actually there
+        Desktop::Unknown if runner.is_installed("gsettings") => Some(Box::new(gnome::Gnome)),
+        Desktop::Unknown => None,
+    }
+}
+
+// The "wallpaper_backend" config value wins over detection, for setups detection gets wrong
+pub fn select_backend(
+    env: &dyn Environment,
+    runner: &dyn CommandRunner,
+    backend_override: Option<&str>,
+) -> std::io::Result<Box<dyn WallpaperBackend>> {
+    if let Some(name) = backend_override {
+        match backend_by_name(name) {
+            Some(backend) => return Ok(backend),
+            None => eprintln!("Unknown wallpaper backend '{}' in config, expected one of {}", name, BACKEND_NAMES.join(", "))
+        }
+    }
+    let desktop = detect_desktop_with(env);
+    backend_for(desktop, runner).ok_or_else(|| std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        format!(
+            "No wallpaper backend found for desktop {:?}, install one of {} or set \"wallpaper_backend\" in the config",
+            desktop,
+            X11_SETTERS.join(", ")
+        )
+    ))
+}
+
+// Backend for a change, which also logs what was detected so setup problems show up in the output
+// The watcher looks the backend up every few seconds through current() and stays quiet
+fn backend_for_change() -> std::io::Result<Box<dyn WallpaperBackend>> {
+    let backend_override = config::load().wallpaper_backend;
+    if backend_override.as_deref().and_then(backend_by_name).is_none() {
+        println!("Linux: detected desktop {:?}", detect_desktop_with(&SystemEnvironment));
+    }
+    select_backend(&SystemEnvironment, &SystemRunner, backend_override.as_deref())
+}
+
+pub fn change(path: &str, options: &ChangeOptions) -> std::io::Result<Output> {
+    let backend = backend_for_change()?;
+    println!("Linux: setting wallpaper with {}", backend.name());
+    let output = backend.set(&SystemRunner, path, options.mode)?;
+    set_lock_screen(backend.as_ref(), path, options);
+    Ok(output)
+}
+
+// The desktop wallpaper has already changed, so a lock screen failure is only reported
+fn set_lock_screen(backend: &dyn WallpaperBackend, path: &str, options: &ChangeOptions) {
+    if options.lock_screen
+        && let Err(e) = backend.set_lock_screen(&SystemRunner, path, options.mode) {
+        eprintln!("Failed to set lock screen image: {}", e);
+    }
+}
+
+pub fn change_for_output(output: &str, path: &str, options: &ChangeOptions) -> std::io::Result<Output> {
+    // Catch typos in the output name up front, the backends' own errors are much less clear
+    match monitors::list_monitors(&SystemEnvironment, &SystemRunner) {
+        Ok(monitors) if !monitors.iter().any(|m| m.name == output) => {
+            let names: Vec<&str> = monitors.iter().map(|m| m.name.as_str()).collect();
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("Output '{}' not found, connected outputs: {}", output, names.join(", "))
+            ));
+        },
+        Ok(_) => {},
+        Err(e) => eprintln!("Could not list monitors to check output '{}': {}", output, e)
+    }
+
+    let backend = backend_for_change()?;
+    println!("Linux: setting wallpaper on {} with {}", output, backend.name());
+    backend.set_for_output(&SystemRunner, output, path, options.mode)
+}
+
+// Splits one image across all monitors, either as a slice per output or one stitched image
+pub fn change_spanned(path: &str, options: &ChangeOptions) -> std::io::Result<Output> {
+    let monitors = monitors::list_monitors(&SystemEnvironment, &SystemRunner)?;
+    let backend = backend_for_change()?;
+
+    let output = if monitors.len() < 2 {
+        println!("Linux: only one monitor, setting the image normally");
+        backend.set(&SystemRunner, path, options.mode)?
+    } else if backend.supports_outputs() {
+        println!("Linux: spanning across {} monitors with {} slices", monitors.len(), backend.name());
+        let slices = span::slice(path, &monitors).map_err(std::io::Error::other)?;
+        let mut last_output = None;
+        for (output, slice_path) in slices {
+            // Each slice is already exactly the size of its monitor
+            last_output = Some(backend.set_for_output(&SystemRunner, &output, &slice_path.to_string_lossy(), FitMode::Fill)?);
+        }
+        last_output.ok_or_else(|| std::io::Error::other("No monitors to span across"))?
+    } else {
+        println!("Linux: spanning across {} monitors with one {} image", monitors.len(), backend.name());
+        let stitched = span::stitch(path, &monitors).map_err(std::io::Error::other)?;
+        backend.set_spanned(&SystemRunner, &stitched.to_string_lossy())?
+    };
+
+    // The lock screen covers a single monitor, so it gets the whole image rather than a slice
+    set_lock_screen(backend.as_ref(), path, options);
+    Ok(output)
+}
+
+pub fn current() -> std::io::Result<Option<String>> {
+    let backend_override = config::load().wallpaper_backend;
+    let backend = select_backend(&SystemEnvironment, &SystemRunner, backend_override.as_deref())?;
+    backend.current(&SystemRunner)
+}
+
+pub fn list_monitors() -> std::io::Result<Vec<monitors::Monitor>> {
+    monitors::list_monitors(&SystemEnvironment, &SystemRunner)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use super::*;
+    use super::system::fake::FakeRunner;
+
+    fn env(vars: &[(&str, &str)]) -> HashMap<String, String> {
+        vars.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect()
+    }
+
+    fn argv(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn desktop_from_names() {
+        assert_eq!(desktop_from("ubuntu:GNOME", "ubuntu"), Desktop::Gnome);
+        assert_eq!(desktop_from("GNOME", ""), Desktop::Gnome);
+        assert_eq!(desktop_from("KDE", "plasma"), Desktop::Kde);
+        assert_eq!(desktop_from("", "plasmawayland"), Desktop::Kde);
+        assert_eq!(desktop_from("Budgie:GNOME", "budgie-desktop"), Desktop::Budgie);
+        assert_eq!(desktop_from("X-Cinnamon", ""), Desktop::Cinnamon);
+        assert_eq!(desktop_from("sway", ""), Desktop::Sway);
+        assert_eq!(desktop_from("Hyprland", ""), Desktop::Hyprland);
+        assert_eq!(desktop_from("river", ""), Desktop::Wlroots);
+        assert_eq!(desktop_from("", "i3"), Desktop::X11Wm);
+        assert_eq!(desktop_from("Enlightenment", "enlightenment"), Desktop::Unknown);
+        assert_eq!(desktop_from("", ""), Desktop::Unknown);
+    }
+
+    #[test]
+    fn detect_prefers_compositor_sockets() {
+        let hyprland = env(&[("HYPRLAND_INSTANCE_SIGNATURE", "abc_123"), ("XDG_CURRENT_DESKTOP", "GNOME")]);
+        assert_eq!(detect_desktop_with(&hyprland), Desktop::Hyprland);
+
+        let sway = env(&[("SWAYSOCK", "/run/user/1000/sway-ipc.sock"), ("XDG_CURRENT_DESKTOP", "")]);
+        assert_eq!(detect_desktop_with(&sway), Desktop::Sway);
+
+        // An empty variable left behind by another session doesn't count
+        let kde = env(&[("SWAYSOCK", ""), ("XDG_CURRENT_DESKTOP", "KDE"), ("WAYLAND_DISPLAY", "wayland-0")]);
+        assert_eq!(detect_desktop_with(&kde), Desktop::Kde);
+
+        let gnome = env(&[("XDG_CURRENT_DESKTOP", "ubuntu:GNOME"), ("DISPLAY", ":0")]);
+        assert_eq!(detect_desktop_with(&gnome), Desktop::Gnome);
+    }
+
+    #[test]
+    fn detect_unknown_desktop() {
+        assert_eq!(detect_desktop_with(&env(&[("DISPLAY", ":0")])), Desktop::X11Wm);
+        assert_eq!(detect_desktop_with(&env(&[("DISPLAY", ":0"), ("WAYLAND_DISPLAY", "wayland-0")])), Desktop::Unknown);
+        assert_eq!(detect_desktop_with(&env(&[])), Desktop::Unknown);
+    }
+
+    #[test]
+    fn select_backend_override_wins() {
+        let gnome = env(&[("XDG_CURRENT_DESKTOP", "GNOME")]);
+        let runner = FakeRunner::default();
+        assert_eq!(select_backend(&gnome, &runner, Some("kde")).unwrap().name(), "kde");
+        assert_eq!(select_backend(&gnome, &runner, Some(" SwayBG ")).unwrap().name(), "sway");
+        // An unknown name falls back to detection
+        assert_eq!(select_backend(&gnome, &runner, Some("nope")).unwrap().name(), "gnome");
+        assert_eq!(select_backend(&gnome, &runner, None).unwrap().name(), "gnome");
+    }
+
+    #[test]
+    fn select_backend_without_desktop() {
+        let x11 = env(&[("DISPLAY", ":0")]);
+        let runner = FakeRunner::with_installed(&["nitrogen", "xwallpaper"]);
+        assert_eq!(select_backend(&x11, &runner, None).unwrap().name(), "xwallpaper");
+
+        let error = select_backend(&x11, &FakeRunner::default(), None).err().unwrap();
+        assert_eq!(error.kind(), std::io::ErrorKind::Unsupported);
+
+        let runner = FakeRunner::with_installed(&["gsettings"]);
+        assert_eq!(select_backend(&env(&[]), &runner, None).unwrap().name(), "gnome");
+        assert!(select_backend(&env(&[]), &FakeRunner::default(), None).is_err());
+    }
+
+    #[test]
+    fn gnome_commands() {
+        let runner = FakeRunner::default();
+        gnome::Gnome.set(&runner, "/home/me/a.jpg", FitMode::Scale).unwrap();
+        gnome::Gnome.set_lock_screen(&runner, "/home/me/a.jpg", FitMode::Fill).unwrap();
+        assert_eq!(runner.calls(), vec![
+            argv(&["gsettings", "set", "org.gnome.desktop.background", "picture-options", "scaled"]),
+            argv(&["gsettings", "set", "org.gnome.desktop.background", "picture-uri", "file:///home/me/a.jpg"]),
+            argv(&["gsettings", "set", "org.gnome.desktop.background", "picture-uri-dark", "file:///home/me/a.jpg"]),
+            argv(&["gsettings", "set", "org.gnome.desktop.screensaver", "picture-options", "zoom"]),
+            argv(&["gsettings", "set", "org.gnome.desktop.screensaver", "picture-uri", "file:///home/me/a.jpg"]),
+        ]);
+    }
+
+    #[test]
+    fn gnome_current_strips_quotes_and_scheme() {
+        let runner = FakeRunner::with_stdout("gsettings", "'file:///home/me/a.jpg'\n");
+        assert_eq!(gnome::Gnome.current(&runner).unwrap().as_deref(), Some("/home/me/a.jpg"));
+        assert_eq!(runner.calls(), vec![argv(&["gsettings", "get", "org.gnome.desktop.background", "picture-uri"])]);
+
+        let runner = FakeRunner::with_stdout("gsettings", "''\n");
+        assert_eq!(gnome::Gnome.current(&runner).unwrap(), None);
+    }
+
+    #[test]
+    fn kde_commands() {
+        let runner = FakeRunner::with_installed(&["plasma-apply-wallpaperimage"]);
+        kde::Kde.set(&runner, "/home/me/a.jpg", FitMode::Fill).unwrap();
+        assert_eq!(runner.calls(), vec![argv(&["plasma-apply-wallpaperimage", "/home/me/a.jpg"])]);
+
+        // Other fill modes need the script, the helper would keep the configured one
+        let runner = FakeRunner::with_installed(&["plasma-apply-wallpaperimage"]);
+        kde::Kde.set(&runner, "/home/me/a \"b\".jpg", FitMode::Scale).unwrap();
+        let calls = runner.calls();
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0][..6], argv(&[
+            "dbus-send", "--session", "--type=method_call", "--dest=org.kde.plasmashell",
+            "/PlasmaShell", "org.kde.PlasmaShell.evaluateScript",
+        ]));
+        assert!(calls[0][6].starts_with("string:"));
+        assert!(calls[0][6].contains("d.writeConfig(\"Image\", \"file:///home/me/a \\\"b\\\".jpg\");"));
+        assert!(calls[0][6].contains("d.writeConfig(\"FillMode\", 1);"));
+    }
+
+    #[test]
+    fn kde_falls_back_to_script_when_helper_fails() {
+        let runner = FakeRunner {
+            installed: vec!["plasma-apply-wallpaperimage"],
+            failing: vec!["plasma-apply-wallpaperimage"],
+            ..Default::default()
+        };
+        kde::Kde.set(&runner, "/home/me/a.jpg", FitMode::Fill).unwrap();
+        let programs: Vec<String> = runner.calls().into_iter().map(|call| call[0].clone()).collect();
+        assert_eq!(programs, argv(&["plasma-apply-wallpaperimage", "dbus-send"]));
+    }
+
+    #[test]
+    fn sway_commands() {
+        let runner = FakeRunner::default();
+        sway::Sway.set(&runner, "/home/me/a.jpg", FitMode::Fill).unwrap();
+        sway::Sway.set_for_output(&runner, "HDMI-A-1", "/home/me/b.jpg", FitMode::Center).unwrap();
+        assert_eq!(runner.calls(), vec![
+            argv(&["swaymsg", "output", "*", "bg", "/home/me/a.jpg", "fill"]),
+            argv(&["swaymsg", "output", "HDMI-A-1", "bg", "/home/me/b.jpg", "center"]),
+        ]);
+    }
+
+    #[test]
+    fn hyprpaper_commands() {
+        let runner = FakeRunner::default();
+        hyprpaper::Hyprpaper.set(&runner, "/home/me/a.jpg", FitMode::Fill).unwrap();
+        hyprpaper::Hyprpaper.set_for_output(&runner, "DP-1", "/home/me/b.jpg", FitMode::Tile).unwrap();
+        assert_eq!(runner.calls(), vec![
+            argv(&["hyprctl", "hyprpaper", "preload", "/home/me/a.jpg"]),
+            argv(&["hyprctl", "hyprpaper", "wallpaper", ",/home/me/a.jpg"]),
+            argv(&["hyprctl", "hyprpaper", "unload", "unused"]),
+            argv(&["hyprctl", "hyprpaper", "preload", "/home/me/b.jpg"]),
+            argv(&["hyprctl", "hyprpaper", "wallpaper", "DP-1,tile:/home/me/b.jpg"]),
+            argv(&["hyprctl", "hyprpaper", "unload", "unused"]),
+        ]);
+    }
+
+    #[test]
+    fn unsupported_backends_refuse_per_output() {
+        let runner = FakeRunner::default();
+        let error = gnome::Gnome.set_for_output(&runner, "DP-1", "/home/me/a.jpg", FitMode::Fill).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::Unsupported);
+        let error = sway::Sway.set_lock_screen(&runner, "/home/me/a.jpg", FitMode::Fill).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::Unsupported);
+        assert!(runner.calls().is_empty());
+    }
+}
@@ -0,0 +1,420 @@
+use serde::{Deserialize, Serialize};
+use super::system::{CommandRunner, Environment};
+use super::{detect_desktop_with, Desktop};
+
+// A connected output, position and size are in layout (logical) pixels
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct Monitor {
+    pub name: String,
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    pub primary: bool,
+}
+
+// Asks whichever tool matches the session, compositors first as they know about outputs xrandr can't see
+pub fn list_monitors(env: &dyn Environment, runner: &dyn CommandRunner) -> std::io::Result<Vec<Monitor>> {
+    match detect_desktop_with(env) {
+        Desktop::Sway => {
+            let output = runner.run("swaymsg", &["-t", "get_outputs", "-r"])?;
+            parse_sway_outputs(&String::from_utf8_lossy(&output.stdout))
+        },
+        Desktop::Hyprland => {
+            let output = runner.run("hyprctl", &["monitors", "-j"])?;
+            parse_hyprland_monitors(&String::from_utf8_lossy(&output.stdout))
+        },
+        _ if env.is_set("WAYLAND_DISPLAY") && runner.is_installed("wlr-randr") => {
+            let output = runner.run("wlr-randr", &[])?;
+            Ok(parse_wlr_randr(&String::from_utf8_lossy(&output.stdout)))
+        },
+        _ => {
+            let output = runner.run("xrandr", &["--query"])?;
+            Ok(parse_xrandr(&String::from_utf8_lossy(&output.stdout)))
+        }
+    }
+}
+
+// e.g. "HDMI-1 connected primary 1920x1080+1920+0 (normal left inverted right x axis y axis) 527mm x 296mm"
+pub fn parse_xrandr(text: &str) -> Vec<Monitor> {
+    let mut monitors = Vec::new();
+    for line in text.lines() {
+        if line.starts_with(char::is_whitespace) {
+            continue;
+        }
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        if tokens.len() < 3 || tokens[1] != "connected" {
+            continue;
+        }
+        // Connected but switched off outputs have no geometry
+        if let Some((width, height, x, y)) = tokens.iter().find_map(|token| parse_geometry(token)) {
+            monitors.push(Monitor {
+                name: tokens[0].to_string(),
+                x,
+                y,
+                width,
+                height,
+                primary: tokens.contains(&"primary"),
+            });
+        }
+    }
+    monitors
+}
+
+// WIDTHxHEIGHT+X+Y
+fn parse_geometry(token: &str) -> Option<(u32, u32, i32, i32)> {
+    let (size, offset) = token.split_once('+')?;
+    let (width, height) = size.split_once('x')?;
+    let (x, y) = offset.split_once('+')?;
+    Some((width.parse().ok()?, height.parse().ok()?, x.parse().ok()?, y.parse().ok()?))
+}
+
+// Blocks start with an unindented "NAME \"Description\"" line followed by indented properties
+pub fn parse_wlr_randr(text: &str) -> Vec<Monitor> {
+    struct Block { name: String, enabled: bool, size: Option<(f64, f64)>, position: (i32, i32), scale: f64, rotated: bool }
+
+    fn finish(block: Block, monitors: &mut Vec<Monitor>) {
+        if let (true, Some((width, height))) = (block.enabled, block.size) {
+            let (width, height) = if block.rotated { (height, width) } else { (width, height) };
+            monitors.push(Monitor {
+                name: block.name,
+                x: block.position.0,
+                y: block.position.1,
+                width: (width / block.scale).round() as u32,
+                height: (height / block.scale).round() as u32,
+                primary: false,
+            });
+        }
+    }
+
+    let mut monitors = Vec::new();
+    let mut current: Option<Block> = None;
+    for line in text.lines() {
+        if !line.starts_with(char::is_whitespace) && !line.trim().is_empty() {
+            if let Some(block) = current.take() {
+                finish(block, &mut monitors);
+            }
+            let name = line.split_whitespace().next().unwrap_or_default().to_string();
+            current = Some(Block { name, enabled: true, size: None, position: (0, 0), scale: 1.0, rotated: false });
+            continue;
+        }
+        let Some(block) = current.as_mut() else { continue };
+        let trimmed = line.trim();
+        if let Some(value) = trimmed.strip_prefix("Enabled:") {
+            block.enabled = value.trim() == "yes";
+        } else if let Some(value) = trimmed.strip_prefix("Position:") {
+            if let Some((x, y)) = value.trim().split_once(',') {
+                block.position = (x.trim().parse().unwrap_or(0), y.trim().parse().unwrap_or(0));
+            }
+        } else if let Some(value) = trimmed.strip_prefix("Scale:") {
+            block.scale = value.trim().parse().ok().filter(|s: &f64| *s > 0.0).unwrap_or(1.0);
+        } else if let Some(value) = trimmed.strip_prefix("Transform:") {
+            block.rotated = matches!(value.trim(), "90" | "270" | "flipped-90" | "flipped-270");
+        } else if trimmed.contains("current") {
+            // e.g. "1920x1080 px, 60.000000 Hz (preferred, current)"
+            if let Some((width, height)) = trimmed.split_whitespace().next().and_then(|size| size.split_once('x'))
+                && let (Ok(width), Ok(height)) = (width.parse(), height.parse()) {
+                block.size = Some((width, height));
+            }
+        }
+    }
+    if let Some(block) = current.take() {
+        finish(block, &mut monitors);
+    }
+    monitors
+}
+
+#[derive(Deserialize)]
+struct SwayOutput {
+    name: String,
+    #[serde(default)]
+    active: bool,
+    #[serde(default)]
+    primary: bool,
+    rect: SwayRect,
+}
+
+#[derive(Deserialize)]
+struct SwayRect {
+    x: i32,
+    y: i32,
+    width: u32,
+    height: u32,
+}
+
+// swaymsg -t get_outputs -r, rect is already in layout pixels
+pub fn parse_sway_outputs(json: &str) -> std::io::Result<Vec<Monitor>> {
+    let outputs: Vec<SwayOutput> = serde_json::from_str(json).map_err(std::io::Error::other)?;
+    Ok(outputs.into_iter()
+        .filter(|output| output.active)
+        .map(|output| Monitor {
+            name: output.name,
+            x: output.rect.x,
+            y: output.rect.y,
+            width: output.rect.width,
+            height: output.rect.height,
+            primary: output.primary,
+        })
+        .collect())
+}
+
+#[derive(Deserialize)]
+struct HyprlandMonitor {
+    name: String,
+    width: u32,
+    height: u32,
+    x: i32,
+    y: i32,
+    #[serde(default = "default_scale")]
+    scale: f64,
+    #[serde(default)]
+    transform: u8,
+    #[serde(default)]
+    disabled: bool,
+}
+
+fn default_scale() -> f64 { 1.0 }
+
+// hyprctl monitors -j, width and height are in physical pixels
+pub fn parse_hyprland_monitors(json: &str) -> std::io::Result<Vec<Monitor>> {
+    let monitors: Vec<HyprlandMonitor> = serde_json::from_str(json).map_err(std::io::Error::other)?;
+    Ok(monitors.into_iter()
+        .filter(|monitor| !monitor.disabled)
+        .map(|monitor| {
+            let scale = if monitor.scale > 0.0 { monitor.scale } else { 1.0 };
+            // Odd transforms are the 90/270 degree rotations
+            let (width, height) = if monitor.transform % 2 == 1 {
+                (monitor.height, monitor.width)
+            } else {
+                (monitor.width, monitor.height)
+            };
+            Monitor {
+                name: monitor.name,
+                x: monitor.x,
+                y: monitor.y,
+                width: (width as f64 / scale).round() as u32,
+                height: (height as f64 / scale).round() as u32,
+                primary: false,
+            }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use super::*;
+    use super::super::system::fake::FakeRunner;
+
+    fn monitor(name: &str, x: i32, y: i32, width: u32, height: u32, primary: bool) -> Monitor {
+        Monitor { name: name.to_string(), x, y, width, height, primary }
+    }
+
+    // Laptop panel, a disconnected port, a monitor rotated to portrait and one that is plugged in but switched off
+    const XRANDR: &str = "\
+Screen 0: minimum 320 x 200, current 3360 x 2560, maximum 16384 x 16384
+eDP-1 connected primary 1920x1080+1440+1480 (normal left inverted right x axis y axis) 344mm x 194mm
+   1920x1080     60.02*+  59.93    48.00
+   1680x1050     59.95
+   1280x1024     60.02
+HDMI-1 disconnected (normal left inverted right x axis y axis)
+DP-1 connected 1440x2560+0+0 left (normal left inverted right x axis y axis) 597mm x 336mm
+   2560x1440     59.95*+  74.97
+   1920x1080     60.00    50.00    59.94
+DP-2 connected (normal left inverted right x axis y axis)
+   1920x1080     60.00 +  74.99    50.00
+";
+
+    #[test]
+    fn xrandr() {
+        assert_eq!(parse_xrandr(XRANDR), vec![
+            monitor("eDP-1", 1440, 1480, 1920, 1080, true),
+            monitor("DP-1", 0, 0, 1440, 2560, false),
+        ]);
+        assert!(parse_xrandr("").is_empty());
+        assert!(parse_xrandr("Screen 0: minimum 320 x 200, current 1920 x 1080, maximum 16384 x 16384\n").is_empty());
+    }
+
+    const WLR_RANDR: &str = "\
+eDP-1 \"BOE 0x0BCA (eDP-1)\"
+  Make: BOE
+  Model: 0x0BCA
+  Serial: (null)
+  Physical size: 290x190 mm
+  Enabled: yes
+  Modes:
+    2256x1504 px, 59.999001 Hz (preferred, current)
+  Position: 0,0
+  Transform: normal
+  Scale: 1.500000
+  Adaptive Sync: disabled
+DP-3 \"Dell Inc. DELL U2719D 8MZ3YS2 (DP-3)\"
+  Make: Dell Inc.
+  Model: DELL U2719D
+  Serial: 8MZ3YS2
+  Physical size: 600x340 mm
+  Enabled: yes
+  Modes:
+    2560x1440 px, 59.951000 Hz (preferred, current)
+    1920x1080 px, 60.000000 Hz
+    1280x720 px, 60.000000 Hz
+  Position: 1504,-500
+  Transform: 90
+  Scale: 1.000000
+  Adaptive Sync: disabled
+HDMI-A-1 \"Samsung Electric Company S24F350 H4ZM000000 (HDMI-A-1)\"
+  Make: Samsung Electric Company
+  Model: S24F350
+  Serial: H4ZM000000
+  Physical size: 530x300 mm
+  Enabled: no
+  Modes:
+    1920x1080 px, 60.000000 Hz (preferred)
+    1920x1080 px, 59.940000 Hz
+";
+
+    #[test]
+    fn wlr_randr() {
+        assert_eq!(parse_wlr_randr(WLR_RANDR), vec![
+            // 2256x1504 at scale 1.5
+            monitor("eDP-1", 0, 0, 1504, 1003, false),
+            monitor("DP-3", 1504, -500, 1440, 2560, false),
+        ]);
+        assert!(parse_wlr_randr("").is_empty());
+    }
+
+    const SWAY: &str = r#"[
+  {
+    "id": 4, "type": "output", "orientation": "none", "percent": 1.0, "urgent": false, "marks": [],
+    "layout": "output", "border": "none", "current_border_width": 0,
+    "rect": { "x": 0, "y": 0, "width": 1280, "height": 720 },
+    "deco_rect": { "x": 0, "y": 0, "width": 0, "height": 0 },
+    "name": "eDP-1", "active": true, "dpms": true, "power": true, "primary": false,
+    "make": "Unknown", "model": "0x38ED", "serial": "0x00000000",
+    "scale": 1.5, "scale_filter": "linear", "transform": "normal", "adaptive_sync_status": "disabled",
+    "current_workspace": "1",
+    "modes": [{ "width": 1920, "height": 1080, "refresh": 60002, "picture_aspect_ratio": "none" }],
+    "current_mode": { "width": 1920, "height": 1080, "refresh": 60002, "picture_aspect_ratio": "none" }
+  },
+  {
+    "id": 5, "type": "output",
+    "rect": { "x": 1280, "y": -420, "width": 1440, "height": 2560 },
+    "name": "DP-1", "active": true, "dpms": true, "power": true, "primary": false,
+    "make": "Dell Inc.", "model": "DELL U2719D", "serial": "8MZ3YS2",
+    "scale": 1.0, "scale_filter": "nearest", "transform": "90", "adaptive_sync_status": "disabled",
+    "current_workspace": "2",
+    "modes": [{ "width": 2560, "height": 1440, "refresh": 59951, "picture_aspect_ratio": "none" }],
+    "current_mode": { "width": 2560, "height": 1440, "refresh": 59951, "picture_aspect_ratio": "none" }
+  },
+  {
+    "id": 6, "type": "output",
+    "rect": { "x": 0, "y": 0, "width": 0, "height": 0 },
+    "name": "HDMI-A-1", "active": false, "dpms": false, "power": false, "primary": false,
+    "make": "Samsung Electric Company", "model": "S24F350", "serial": "H4ZM000000",
+    "modes": [{ "width": 1920, "height": 1080, "refresh": 60000, "picture_aspect_ratio": "none" }]
+  }
+]"#;
+
+    #[test]
+    fn sway_outputs() {
+        assert_eq!(parse_sway_outputs(SWAY).unwrap(), vec![
+            monitor("eDP-1", 0, 0, 1280, 720, false),
+            monitor("DP-1", 1280, -420, 1440, 2560, false),
+        ]);
+        assert!(parse_sway_outputs("[]").unwrap().is_empty());
+        assert!(parse_sway_outputs("").is_err());
+    }
+
+    const HYPRLAND: &str = r#"[{
+    "id": 0,
+    "name": "DP-1",
+    "description": "Dell Inc. DELL U2720Q 8FXQ913",
+    "make": "Dell Inc.",
+    "model": "DELL U2720Q",
+    "serial": "8FXQ913",
+    "width": 3840,
+    "height": 2160,
+    "refreshRate": 59.99700,
+    "x": 0,
+    "y": 0,
+    "activeWorkspace": { "id": 1, "name": "1" },
+    "specialWorkspace": { "id": 0, "name": "" },
+    "reserved": [0, 30, 0, 0],
+    "scale": 1.50,
+    "transform": 0,
+    "focused": true,
+    "dpmsStatus": true,
+    "vrr": false,
+    "activelyTearing": false,
+    "disabled": false,
+    "currentFormat": "XRGB8888",
+    "availableModes": ["3840x2160@60.00Hz", "2560x1440@59.95Hz"]
+},{
+    "id": 1,
+    "name": "HDMI-A-1",
+    "description": "Samsung Electric Company S24F350 H4ZM000000",
+    "make": "Samsung Electric Company",
+    "model": "S24F350",
+    "serial": "H4ZM000000",
+    "width": 1920,
+    "height": 1080,
+    "refreshRate": 60.00000,
+    "x": -1080,
+    "y": -200,
+    "activeWorkspace": { "id": 2, "name": "2" },
+    "specialWorkspace": { "id": 0, "name": "" },
+    "reserved": [0, 0, 0, 0],
+    "scale": 1.00,
+    "transform": 3,
+    "focused": false,
+    "dpmsStatus": true,
+    "vrr": false,
+    "activelyTearing": false,
+    "disabled": false,
+    "currentFormat": "XRGB8888",
+    "availableModes": ["1920x1080@60.00Hz"]
+},{
+    "id": 2,
+    "name": "eDP-1",
+    "width": 2256,
+    "height": 1504,
+    "x": 0,
+    "y": 0,
+    "scale": 1.00,
+    "transform": 0,
+    "disabled": true
+}]"#;
+
+    #[test]
+    fn hyprland_monitors() {
+        assert_eq!(parse_hyprland_monitors(HYPRLAND).unwrap(), vec![
+            monitor("DP-1", 0, 0, 2560, 1440, false),
+            monitor("HDMI-A-1", -1080, -200, 1080, 1920, false),
+        ]);
+        assert!(parse_hyprland_monitors("[]").unwrap().is_empty());
+        assert!(parse_hyprland_monitors("").is_err());
+    }
+
+    #[test]
+    fn list_monitors_asks_the_session() {
+        let env: HashMap<String, String> = HashMap::from([("SWAYSOCK".to_string(), "/run/user/1000/sway.sock".to_string())]);
+        let runner = FakeRunner::with_stdout("swaymsg", SWAY);
+        assert_eq!(list_monitors(&env, &runner).unwrap().len(), 2);
+        assert_eq!(runner.calls(), vec![vec!["swaymsg", "-t", "get_outputs", "-r"]]);
+
+        let env: HashMap<String, String> = HashMap::from([("HYPRLAND_INSTANCE_SIGNATURE".to_string(), "abc".to_string())]);
+        let runner = FakeRunner::with_stdout("hyprctl", HYPRLAND);
+        assert_eq!(list_monitors(&env, &runner).unwrap().len(), 2);
+        assert_eq!(runner.calls(), vec![vec!["hyprctl", "monitors", "-j"]]);
+
+        let env: HashMap<String, String> = HashMap::from([("WAYLAND_DISPLAY".to_string(), "wayland-1".to_string())]);
+        let runner = FakeRunner { installed: vec!["wlr-randr"], ..FakeRunner::with_stdout("wlr-randr", WLR_RANDR) };
+        assert_eq!(list_monitors(&env, &runner).unwrap().len(), 2);
+        assert_eq!(runner.calls(), vec![vec!["wlr-randr"]]);
+
+        let env: HashMap<String, String> = HashMap::from([("DISPLAY".to_string(), ":0".to_string())]);
+        let runner = FakeRunner::with_stdout("xrandr", XRANDR);
+        assert_eq!(list_monitors(&env, &runner).unwrap().len(), 2);
+        assert_eq!(runner.calls(), vec![vec!["xrandr", "--query"]]);
+    }
+}
@@ -0,0 +1,26 @@
+use std::process::Output;
+use crate::wallpaper::FitMode;
+use super::system::CommandRunner;
+use super::WallpaperBackend;
+
+// --save stores the choice in nitrogen's bg-saved.cfg, which `nitrogen --restore` reapplies
+pub struct Nitrogen;
+
+impl WallpaperBackend for Nitrogen {
+    fn name(&self) -> &'static str {
+        "nitrogen"
+    }
+
+    fn set(&self, runner: &dyn CommandRunner, path: &str, mode: FitMode) -> std::io::Result<Output> {
+        runner.run("nitrogen", &[nitrogen_mode(mode), "--save", path])
+    }
+}
+
+fn nitrogen_mode(mode: FitMode) -> &'static str {
+    match mode {
+        FitMode::Fill => "--set-zoom-fill",
+        FitMode::Scale => "--set-zoom",
+        FitMode::Center => "--set-centered",
+        FitMode::Tile => "--set-tiled",
+    }
+}
@@ -0,0 +1,34 @@
+use std::process::Output;
+use crate::wallpaper::FitMode;
+use super::system::CommandRunner;
+use super::WallpaperBackend;
+
+// Sway starts swaybg itself when an output background is changed over IPC
+pub struct Sway;
+
+impl WallpaperBackend for Sway {
+    fn name(&self) -> &'static str {
+        "sway"
+    }
+
+    fn set(&self, runner: &dyn CommandRunner, path: &str, mode: FitMode) -> std::io::Result<Output> {
+        runner.run("swaymsg", &["output", "*", "bg", path, sway_mode(mode)])
+    }
+
+    fn supports_outputs(&self) -> bool {
+        true
+    }
+
+    fn set_for_output(&self, runner: &dyn CommandRunner, output: &str, path: &str, mode: FitMode) -> std::io::Result<Output> {
+        runner.run("swaymsg", &["output", output, "bg", path, sway_mode(mode)])
+    }
+}
+
+fn sway_mode(mode: FitMode) -> &'static str {
+    match mode {
+        FitMode::Fill => "fill",
+        FitMode::Scale => "fit",
+        FitMode::Center => "center",
+        FitMode::Tile => "tile",
+    }
+}
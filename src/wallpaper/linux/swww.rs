@@ -0,0 +1,44 @@
+use std::process::Output;
+use crate::wallpaper::FitMode;
+use super::system::CommandRunner;
+use super::WallpaperBackend;
+
+// swww works on any wlroots compositor, but needs swww-daemon to be running
+pub struct Swww;
+
+impl WallpaperBackend for Swww {
+    fn name(&self) -> &'static str {
+        "swww"
+    }
+
+    fn set(&self, runner: &dyn CommandRunner, path: &str, mode: FitMode) -> std::io::Result<Output> {
+        runner.run("swww", &["img", "--resize", swww_resize(mode), path])
+    }
+
+    fn supports_outputs(&self) -> bool {
+        true
+    }
+
+    fn set_for_output(&self, runner: &dyn CommandRunner, output: &str, path: &str, mode: FitMode) -> std::io::Result<Output> {
+        runner.run("swww", &["img", "--outputs", output, "--resize", swww_resize(mode), path])
+    }
+
+    // e.g. "DP-1: 2560x1440, scale: 1, currently displaying: image: /home/me/image.jpg"
+    fn current(&self, runner: &dyn CommandRunner) -> std::io::Result<Option<String>> {
+        let output = runner.run("swww", &["query"])?;
+        let images: Vec<String> = String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter_map(|line| line.split_once("image: ").map(|(_, image)| image.trim().to_string()))
+            .collect();
+        Ok(Some(images.join(", ")).filter(|images| !images.is_empty()))
+    }
+}
+
+// swww can't tile, the closest is showing the image at its original size
+fn swww_resize(mode: FitMode) -> &'static str {
+    match mode {
+        FitMode::Fill => "crop",
+        FitMode::Scale => "fit",
+        FitMode::Center | FitMode::Tile => "no",
+    }
+}
@@ -0,0 +1,112 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::process::{Command, Output};
+
+// Seams between the backends and the machine they run on,
+// so detection and the commands a backend issues can be checked without a real desktop
+
+pub trait Environment {
+    fn var(&self, key: &str) -> Option<String>;
+
+    fn is_set(&self, key: &str) -> bool {
+        self.var(key).is_some_and(|value| !value.is_empty())
+    }
+}
+
+pub trait CommandRunner {
+    // Runs the program and returns an error if it can't be started or exits with a non-zero status
+    fn run(&self, program: &str, args: &[&str]) -> std::io::Result<Output>;
+    fn is_installed(&self, program: &str) -> bool;
+}
+
+// The real process environment
+pub struct SystemEnvironment;
+
+impl Environment for SystemEnvironment {
+    fn var(&self, key: &str) -> Option<String> {
+        std::env::var(key).ok()
+    }
+}
+
+// A fixed set of variables, e.g. a snapshot of another session
+impl Environment for HashMap<String, String> {
+    fn var(&self, key: &str) -> Option<String> {
+        self.get(key).cloned()
+    }
+}
+
+// Spawns real processes
+pub struct SystemRunner;
+
+impl CommandRunner for SystemRunner {
+    fn run(&self, program: &str, args: &[&str]) -> std::io::Result<Output> {
+        let output = Command::new(program).args(args).output()?;
+        if !output.status.success() {
+            return Err(std::io::Error::other(format!(
+                "{} failed with {}: {}",
+                program,
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(output)
+    }
+
+    // Looks for an executable with this name on the PATH
+    fn is_installed(&self, program: &str) -> bool {
+        match std::env::var_os("PATH") {
+            Some(paths) => std::env::split_paths(&paths).any(|dir| Path::new(&dir).join(program).is_file()),
+            None => false
+        }
+    }
+}
+
+// Records every command instead of running it, the backend and monitor tests share it
+#[cfg(test)]
+pub mod fake {
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+    use std::process::{ExitStatus, Output};
+    use super::CommandRunner;
+
+    #[derive(Default)]
+    pub struct FakeRunner {
+        pub installed: Vec<&'static str>,
+        // stdout per program, everything else prints nothing
+        pub stdout: HashMap<&'static str, String>,
+        pub failing: Vec<&'static str>,
+        pub calls: RefCell<Vec<Vec<String>>>,
+    }
+
+    impl FakeRunner {
+        pub fn with_installed(installed: &[&'static str]) -> Self {
+            FakeRunner { installed: installed.to_vec(), ..Default::default() }
+        }
+
+        pub fn with_stdout(program: &'static str, stdout: &str) -> Self {
+            FakeRunner { stdout: HashMap::from([(program, stdout.to_string())]), ..Default::default() }
+        }
+
+        pub fn calls(&self) -> Vec<Vec<String>> {
+            self.calls.borrow().clone()
+        }
+    }
+
+    impl CommandRunner for FakeRunner {
+        fn run(&self, program: &str, args: &[&str]) -> std::io::Result<Output> {
+            self.calls.borrow_mut().push(std::iter::once(program).chain(args.iter().copied()).map(str::to_string).collect());
+            if self.failing.contains(&program) {
+                return Err(std::io::Error::other(format!("{} failed", program)));
+            }
+            Ok(Output {
+                status: ExitStatus::default(),
+                stdout: self.stdout.get(program).cloned().unwrap_or_default().into_bytes(),
+                stderr: Vec::new(),
+            })
+        }
+
+        fn is_installed(&self, program: &str) -> bool {
+            self.installed.contains(&program)
+        }
+    }
+}
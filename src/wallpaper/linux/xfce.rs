@@ -0,0 +1,80 @@
+use std::process::Output;
+use crate::wallpaper::FitMode;
+use super::system::CommandRunner;
+use super::WallpaperBackend;
+
+const CHANNEL: &str = "xfce4-desktop";
+// Used when xfdesktop hasn't written any backdrop properties yet
+const DEFAULT_PROPERTY: &str = "/backdrop/screen0/monitor0/workspace0/last-image";
+
+// xfdesktop keeps one image per screen/monitor/workspace, so every last-image property is updated
+pub struct Xfce;
+
+impl WallpaperBackend for Xfce {
+    fn name(&self) -> &'static str {
+        "xfce"
+    }
+
+    fn set(&self, runner: &dyn CommandRunner, path: &str, mode: FitMode) -> std::io::Result<Output> {
+        let listing = runner.run("xfconf-query", &["-c", CHANNEL, "-l"])?;
+        let properties = last_image_properties(&String::from_utf8_lossy(&listing.stdout));
+
+        if properties.is_empty() {
+            runner.run("xfconf-query", &["-c", CHANNEL, "-p", DEFAULT_PROPERTY, "-n", "-t", "string", "-s", path])?;
+            let style = DEFAULT_PROPERTY.replace("last-image", "image-style");
+            return runner.run("xfconf-query", &["-c", CHANNEL, "-p", &style, "-n", "-t", "int", "-s", image_style(mode)]);
+        }
+
+        set_properties(runner, listing, &properties, path, mode)
+    }
+
+    fn supports_outputs(&self) -> bool {
+        true
+    }
+
+    fn set_for_output(&self, runner: &dyn CommandRunner, output: &str, path: &str, mode: FitMode) -> std::io::Result<Output> {
+        let listing = runner.run("xfconf-query", &["-c", CHANNEL, "-l"])?;
+        let monitor = format!("/monitor{}/", output);
+        let properties: Vec<String> = last_image_properties(&String::from_utf8_lossy(&listing.stdout))
+            .into_iter()
+            .filter(|property| property.contains(&monitor))
+            .collect();
+
+        if properties.is_empty() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("xfdesktop has no backdrop for output {}", output)
+            ));
+        }
+        set_properties(runner, listing, &properties, path, mode)
+    }
+}
+
+fn set_properties(runner: &dyn CommandRunner, listing: Output, properties: &[String], path: &str, mode: FitMode) -> std::io::Result<Output> {
+    let mut last_output = listing;
+    for property in properties {
+        last_output = runner.run("xfconf-query", &["-c", CHANNEL, "-p", property, "-s", path])?;
+        let style = property.replace("last-image", "image-style");
+        runner.run("xfconf-query", &["-c", CHANNEL, "-p", &style, "-n", "-t", "int", "-s", image_style(mode)])?;
+    }
+    Ok(last_output)
+}
+
+// e.g. /backdrop/screen0/monitorHDMI-1/workspace0/last-image
+pub fn last_image_properties(listing: &str) -> Vec<String> {
+    listing.lines()
+        .map(str::trim)
+        .filter(|line| line.starts_with("/backdrop/screen") && line.ends_with("/last-image"))
+        .map(str::to_string)
+        .collect()
+}
+
+// Values of xfdesktop's image-style property
+fn image_style(mode: FitMode) -> &'static str {
+    match mode {
+        FitMode::Fill => "5",   // Zoomed
+        FitMode::Scale => "4",  // Scaled
+        FitMode::Center => "1", // Centered
+        FitMode::Tile => "2",   // Tiled
+    }
+}
@@ -0,0 +1,54 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::Output;
+use crate::wallpaper::FitMode;
+use super::system::CommandRunner;
+use super::WallpaperBackend;
+
+const RESTORE_SCRIPT_NAME: &str = ".xwallpaperbg";
+
+// xwallpaper has no memory of its own, so a ~/.xwallpaperbg script is written the same way feh writes ~/.fehbg
+pub struct Xwallpaper;
+
+impl WallpaperBackend for Xwallpaper {
+    fn name(&self) -> &'static str {
+        "xwallpaper"
+    }
+
+    fn set(&self, runner: &dyn CommandRunner, path: &str, mode: FitMode) -> std::io::Result<Output> {
+        let output = runner.run("xwallpaper", &[xwallpaper_mode(mode), path])?;
+        if let Err(e) = write_restore_script(path, mode) {
+            eprintln!("Failed to write {}: {}", RESTORE_SCRIPT_NAME, e);
+        }
+        Ok(output)
+    }
+}
+
+fn xwallpaper_mode(mode: FitMode) -> &'static str {
+    match mode {
+        FitMode::Fill => "--zoom",
+        FitMode::Scale => "--maximize",
+        FitMode::Center => "--center",
+        FitMode::Tile => "--tile",
+    }
+}
+
+fn write_restore_script(path: &str, mode: FitMode) -> std::io::Result<PathBuf> {
+    let home = dirs::home_dir().ok_or_else(|| std::io::Error::new(
+        std::io::ErrorKind::NotFound,
+        "Could not find the home directory"
+    ))?;
+    let script_path = home.join(RESTORE_SCRIPT_NAME);
+
+    // Single quote the path for the shell, closing and reopening the quotes around any ' in it
+    let quoted = format!("'{}'", path.replace('\'', "'\\''"));
+    let script = format!("#!/bin/sh\nxwallpaper {} {}\n", xwallpaper_mode(mode), quoted);
+    fs::write(&script_path, script)?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&script_path, fs::Permissions::from_mode(0o755))?;
+    }
+    Ok(script_path)
+}
@@ -1,21 +1,89 @@
 use std::path::Path;
 use std::process::Output;
-use crate::utils;
+use std::str::FromStr;
+use serde::{Deserialize, Serialize};
+use crate::{config, history, state, utils};
 use crate::utils::os::OperatingSystem;
 
 pub mod linux;
 pub mod windows;
+pub mod span;
 
+// How the image is fitted to the screen
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum FitMode {
+    // Cover the whole screen, cropping the edges if the ratio differs
+    #[default]
+    Fill,
+    // Show the whole image, leaving bars if the ratio differs
+    Scale,
+    // Original size in the middle of the screen
+    Center,
+    // Repeat the image at its original size
+    Tile,
+}
+
+impl FromStr for FitMode {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_lowercase().as_str() {
+            "fill" | "zoom" | "crop" => Ok(FitMode::Fill),
+            "scale" | "scaled" | "fit" | "max" => Ok(FitMode::Scale),
+            "center" | "centered" => Ok(FitMode::Center),
+            "tile" | "tiled" | "wallpaper" => Ok(FitMode::Tile),
+            other => Err(format!("Invalid fit mode '{}', expected one of fill, scale, center, tile", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ChangeOptions {
+    pub mode: FitMode,
+    // Also use the image for the lock screen where the desktop supports it
+    pub lock_screen: bool,
+    // Split the image across all monitors instead of repeating it on each
+    pub span: bool,
+}
+
+impl ChangeOptions {
+    pub fn from_config() -> Self {
+        let config = config::load();
+        ChangeOptions {
+            mode: config.fit_mode.unwrap_or_default(),
+            lock_screen: config.set_lock_screen,
+            span: config.span_monitors,
+        }
+    }
+}
+
+// Uses the fit mode and lock screen settings from the config
 pub fn change(path: &str) -> std::io::Result<Output> {
-    
-    if !Path::new(&path).exists() {
-        // If path does not exist
-        eprintln!("File does not exist {}", &path);
-        panic!("File {} does not exist", &path);
+    change_with_options(path, &ChangeOptions::from_config())
+}
+
+pub fn change_with_options(path: &str, options: &ChangeOptions) -> std::io::Result<Output> {
+    let output = set(path, options)?;
+    history::record(path, None);
+    Ok(output)
+}
+
+// Puts an earlier wallpaper back without adding it to the history again
+pub fn reapply(path: &str, output: Option<&str>) -> std::io::Result<Output> {
+    let options = ChangeOptions::from_config();
+    match output {
+        Some(output) => set_for_output(output, path, &options),
+        None => set(path, &options),
     }
+}
 
-    match utils::os::get_operating_system() {
-        OperatingSystem::Linux => linux::gnome(path),
+fn set(path: &str, options: &ChangeOptions) -> std::io::Result<Output> {
+    check_exists(path)?;
+
+    let output = match utils::os::get_operating_system() {
+        OperatingSystem::Linux if options.span => linux::change_spanned(path, options),
+        OperatingSystem::Linux => linux::change(path, options),
         OperatingSystem::Windows => windows::explorer(path),
         OperatingSystem::MacIntel | OperatingSystem::MacArm => std::io::Result::Err(std::io::Error::new(
             std::io::ErrorKind::Unsupported,
@@ -25,5 +93,62 @@ pub fn change(path: &str) -> std::io::Result<Output> {
             std::io::ErrorKind::Unsupported,
             "Unknown operating system"
         )),
+    }?;
+    state::set_current(path, None);
+    Ok(output)
+}
+
+// Sets the wallpaper on a single monitor, output is a name from list_monitors() e.g. "HDMI-1"
+pub fn change_for_output(output: &str, path: &str) -> std::io::Result<Output> {
+    change_for_output_with_options(output, path, &ChangeOptions::from_config())
+}
+
+pub fn change_for_output_with_options(output: &str, path: &str, options: &ChangeOptions) -> std::io::Result<Output> {
+    let result = set_for_output(output, path, options)?;
+    history::record(path, Some(output));
+    Ok(result)
+}
+
+fn set_for_output(output: &str, path: &str, options: &ChangeOptions) -> std::io::Result<Output> {
+    check_exists(path)?;
+
+    let result = match utils::os::get_operating_system() {
+        OperatingSystem::Linux => linux::change_for_output(output, path, options),
+        _ => Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "Per-monitor wallpapers are only supported on Linux"
+        )),
+    }?;
+    state::set_current(path, Some(output));
+    Ok(result)
+}
+
+// What the desktop reports as its wallpaper, None where that can't be read back
+pub fn current() -> std::io::Result<Option<String>> {
+    match utils::os::get_operating_system() {
+        OperatingSystem::Linux => linux::current(),
+        _ => Ok(None),
+    }
+}
+
+pub fn list_monitors() -> std::io::Result<Vec<linux::monitors::Monitor>> {
+    match utils::os::get_operating_system() {
+        OperatingSystem::Linux => linux::list_monitors(),
+        _ => Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "Listing monitors is only supported on Linux"
+        )),
+    }
+}
+
+fn check_exists(path: &str) -> std::io::Result<()> {
+    if !Path::new(&path).exists() {
+        // If path does not exist
+        eprintln!("File does not exist {}", &path);
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("File {} does not exist", &path)
+        ));
     }
-}
\ No newline at end of file
+    Ok(())
+}
@@ -0,0 +1,182 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::DynamicImage;
+use crate::utils;
+use super::linux::monitors::Monitor;
+
+// Splitting one wide image across several monitors
+// The image is scaled to cover the bounding box of the whole monitor layout, then each
+// monitor gets the part of it that sits behind the monitor's position in the layout
+
+const JPEG_QUALITY: u8 = 92;
+
+// Where the generated slices are written, they are regenerated on every change
+pub fn get_spanned_directory() -> PathBuf {
+    utils::get_user_data_directory().join("spanned")
+}
+
+// (x, y, width, height) of the rectangle holding every monitor
+pub fn layout_bounds(monitors: &[Monitor]) -> Option<(i32, i32, u32, u32)> {
+    let min_x = monitors.iter().map(|m| m.x).min()?;
+    let min_y = monitors.iter().map(|m| m.y).min()?;
+    let max_x = monitors.iter().map(|m| m.x + m.width as i32).max()?;
+    let max_y = monitors.iter().map(|m| m.y + m.height as i32).max()?;
+    Some((min_x, min_y, (max_x - min_x) as u32, (max_y - min_y) as u32))
+}
+
+// Maps the layout onto the image: returns the scale from image to layout pixels and
+// the image offset (in image pixels) of the layout's top left corner
+fn cover(image_width: u32, image_height: u32, layout_width: u32, layout_height: u32) -> (f64, f64, f64) {
+    let scale = f64::max(
+        layout_width as f64 / image_width as f64,
+        layout_height as f64 / image_height as f64,
+    );
+    let offset_x = (image_width as f64 - layout_width as f64 / scale) / 2.0;
+    let offset_y = (image_height as f64 - layout_height as f64 / scale) / 2.0;
+    (scale, offset_x, offset_y)
+}
+
+// The (x, y, width, height) in image pixels of the region behind a layout rectangle
+fn crop_rect(image_width: u32, image_height: u32, monitors: &[Monitor], x: i32, y: i32, width: u32, height: u32) -> Option<(u32, u32, u32, u32)> {
+    let (min_x, min_y, layout_width, layout_height) = layout_bounds(monitors)?;
+    let (scale, offset_x, offset_y) = cover(image_width, image_height, layout_width, layout_height);
+
+    let crop_x = (offset_x + (x - min_x) as f64 / scale).round().max(0.0) as u32;
+    let crop_y = (offset_y + (y - min_y) as f64 / scale).round().max(0.0) as u32;
+    let crop_width = ((width as f64 / scale).round() as u32).clamp(1, image_width.saturating_sub(crop_x).max(1));
+    let crop_height = ((height as f64 / scale).round() as u32).clamp(1, image_height.saturating_sub(crop_y).max(1));
+    Some((crop_x, crop_y, crop_width, crop_height))
+}
+
+// Crops the image region that sits behind a layout rectangle, resized to that rectangle's size
+fn crop_region(image: &DynamicImage, monitors: &[Monitor], x: i32, y: i32, width: u32, height: u32) -> Option<DynamicImage> {
+    let (crop_x, crop_y, crop_width, crop_height) = crop_rect(image.width(), image.height(), monitors, x, y, width, height)?;
+    Some(image
+        .crop_imm(crop_x, crop_y, crop_width, crop_height)
+        .resize_exact(width, height, FilterType::Lanczos3))
+}
+
+// Writes one slice per monitor, returns (output name, slice path) pairs
+pub fn slice(path: &str, monitors: &[Monitor]) -> Result<Vec<(String, PathBuf)>, Box<dyn Error + Send + Sync>> {
+    let image = image::open(path)?;
+    let out_dir = get_spanned_directory();
+    utils::ensure_dir(&out_dir)?;
+
+    let stem = file_stem(path);
+    let mut slices = Vec::new();
+    for monitor in monitors {
+        let region = crop_region(&image, monitors, monitor.x, monitor.y, monitor.width, monitor.height)
+            .ok_or("No monitors to span across")?;
+        let slice_path = out_dir.join(format!("{}-{}.jpg", stem, utils::sanitize_tag_name(&monitor.name)));
+        save_jpeg(&region, &slice_path)?;
+        slices.push((monitor.name.clone(), slice_path));
+    }
+    Ok(slices)
+}
+
+// Writes a single image covering the whole layout, for desktops with a "spanned" picture option
+pub fn stitch(path: &str, monitors: &[Monitor]) -> Result<PathBuf, Box<dyn Error + Send + Sync>> {
+    let (min_x, min_y, width, height) = layout_bounds(monitors).ok_or("No monitors to span across")?;
+    let image = image::open(path)?;
+    let region = crop_region(&image, monitors, min_x, min_y, width, height)
+        .ok_or("No monitors to span across")?;
+
+    let out_dir = get_spanned_directory();
+    utils::ensure_dir(&out_dir)?;
+    let stitched_path = out_dir.join(format!("{}-spanned.jpg", file_stem(path)));
+    save_jpeg(&region, &stitched_path)?;
+    Ok(stitched_path)
+}
+
+fn save_jpeg(image: &DynamicImage, path: &Path) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let writer = BufWriter::new(File::create(path)?);
+    // JPEG has no alpha channel
+    image.to_rgb8().write_with_encoder(JpegEncoder::new_with_quality(writer, JPEG_QUALITY))?;
+    Ok(())
+}
+
+fn file_stem(path: &str) -> String {
+    Path::new(path)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_else(|| "wallpaper".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{Rgb, RgbImage};
+    use super::*;
+    use crate::wallpaper::linux::monitors::parse_hyprland_monitors;
+
+    fn monitor(name: &str, x: i32, y: i32, width: u32, height: u32) -> Monitor {
+        Monitor { name: name.to_string(), x, y, width, height, primary: false }
+    }
+
+    #[test]
+    fn bounds_with_negative_offsets() {
+        // A portrait monitor left of the primary one, its top edge above it
+        let monitors = [monitor("DP-2", -1440, -400, 1440, 2560), monitor("DP-1", 0, 0, 1920, 1080)];
+        assert_eq!(layout_bounds(&monitors), Some((-1440, -400, 3360, 2560)));
+        assert_eq!(layout_bounds(&[]), None);
+    }
+
+    #[test]
+    fn cover_scales_to_the_larger_side() {
+        assert_eq!(cover(3840, 1080, 3840, 1080), (1.0, 0.0, 0.0));
+        // Too narrow for the layout: scaled up to the width, the extra height is cut evenly
+        assert_eq!(cover(1920, 1080, 3840, 1080), (2.0, 0.0, 270.0));
+        // Too wide: scaled down to the height, the extra width is cut evenly
+        assert_eq!(cover(7680, 2160, 1920, 1080), (0.5, 1920.0, 0.0));
+    }
+
+    #[test]
+    fn side_by_side_slices() {
+        let monitors = [monitor("HDMI-1", 0, 0, 1920, 1080), monitor("DP-1", 1920, 0, 1920, 1080)];
+        assert_eq!(crop_rect(3840, 1080, &monitors, 0, 0, 1920, 1080), Some((0, 0, 1920, 1080)));
+        assert_eq!(crop_rect(3840, 1080, &monitors, 1920, 0, 1920, 1080), Some((1920, 0, 1920, 1080)));
+        // Half the resolution maps to half the pixels
+        assert_eq!(crop_rect(1920, 540, &monitors, 1920, 0, 1920, 1080), Some((960, 0, 960, 540)));
+    }
+
+    #[test]
+    fn negative_offsets_are_relative_to_the_layout() {
+        let monitors = [monitor("DP-2", -1920, 0, 1920, 1080), monitor("DP-1", 0, 0, 1920, 1080)];
+        assert_eq!(crop_rect(3840, 1080, &monitors, -1920, 0, 1920, 1080), Some((0, 0, 1920, 1080)));
+        assert_eq!(crop_rect(3840, 1080, &monitors, 0, 0, 1920, 1080), Some((1920, 0, 1920, 1080)));
+
+        // Raised left monitor: the layout is 3840x1480, centred in a 3840x2160 image that leaves 340 rows above it
+        let monitors = [monitor("DP-2", -1920, -400, 1920, 1080), monitor("DP-1", 0, 0, 1920, 1080)];
+        assert_eq!(crop_rect(3840, 2160, &monitors, -1920, -400, 1920, 1080), Some((0, 340, 1920, 1080)));
+        assert_eq!(crop_rect(3840, 2160, &monitors, 0, 0, 1920, 1080), Some((1920, 740, 1920, 1080)));
+    }
+
+    #[test]
+    fn mixed_scale_uses_layout_pixels() {
+        // A 4K panel at scale 2 next to a 1440p one at scale 1, as hyprctl reports them
+        let monitors = parse_hyprland_monitors(r#"[
+            {"name": "eDP-1", "width": 3840, "height": 2160, "x": 0, "y": 0, "scale": 2.00, "transform": 0, "disabled": false},
+            {"name": "DP-1", "width": 2560, "height": 1440, "x": 1920, "y": 0, "scale": 1.00, "transform": 0, "disabled": false}
+        ]"#).unwrap();
+        assert_eq!(layout_bounds(&monitors), Some((0, 0, 4480, 1440)));
+        assert_eq!(crop_rect(4480, 1440, &monitors, 0, 0, 1920, 1080), Some((0, 0, 1920, 1080)));
+        assert_eq!(crop_rect(4480, 1440, &monitors, 1920, 0, 2560, 1440), Some((1920, 0, 2560, 1440)));
+    }
+
+    #[test]
+    fn crop_region_takes_the_matching_part() {
+        // Left half red, right half blue
+        let image = DynamicImage::ImageRgb8(RgbImage::from_fn(200, 100, |x, _| {
+            if x < 100 { Rgb([255, 0, 0]) } else { Rgb([0, 0, 255]) }
+        }));
+        let monitors = [monitor("DP-2", -50, 0, 50, 50), monitor("DP-1", 0, 0, 50, 50)];
+        let left = crop_region(&image, &monitors, -50, 0, 50, 50).unwrap().to_rgb8();
+        let right = crop_region(&image, &monitors, 0, 0, 50, 50).unwrap().to_rgb8();
+        assert_eq!(left.dimensions(), (50, 50));
+        assert_eq!(*left.get_pixel(25, 25), Rgb([255, 0, 0]));
+        assert_eq!(*right.get_pixel(25, 25), Rgb([0, 0, 255]));
+    }
+}